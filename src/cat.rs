@@ -0,0 +1,384 @@
+use crate::format::ansi;
+use crate::format::asciicast::{self, Event, EventCode};
+use crate::format::raw;
+use crate::render;
+use anyhow::bail;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::iter;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// Output of the recording, as it was displayed
+    Raw,
+    /// All events, one JSON array per line
+    Json,
+    /// Text of the final screen and of the lines which scrolled off it, with
+    /// colors and text attributes as SGR sequences
+    TextSgr,
+}
+
+/// Part of a recording to print, as a number of events (which aren't lines,
+/// an event may hold any amount of output).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sample {
+    Head(usize),
+    Tail(usize),
+}
+
+/// Prints output of the recordings, one after another, optionally starting
+/// with a clear screen, or as plain text with escape sequences stripped.
+/// `-` reads a recording from stdin. Output goes to stdout unless `output`
+/// names a file other than `-`.
+///
+/// With `Format::Json` events of every kind are printed instead, with time
+/// relative to the previous event if `relative_timestamps` is set. With
+/// `Format::TextSgr` the output gets replayed into a terminal, and what it
+/// ends up displaying is printed.
+///
+/// `sample` applies to each recording separately, counting the events that
+/// get printed, i.e. only output ones unless printing JSON.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    filenames: &[String],
+    output: Option<&str>,
+    overwrite: bool,
+    reset: bool,
+    strip_escapes: bool,
+    format: Format,
+    relative_timestamps: bool,
+    sample: Option<Sample>,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    let mut sink: Box<dyn Write> = match output {
+        Some(path) if path != "-" => Box::new(io::BufWriter::new(create(path, overwrite)?)),
+        _ => Box::new(io::stdout().lock()),
+    };
+
+    if reset {
+        sink.write_all(raw::RESET)?;
+    }
+
+    for filename in filenames {
+        let reader: Box<dyn io::BufRead> = match filename.as_str() {
+            "-" => Box::new(io::stdin().lock()),
+            _ => Box::new(io::BufReader::new(fs::File::open(filename)?)),
+        };
+
+        match format {
+            Format::Raw => {
+                let mut stripper = strip_escapes.then(ansi::Stripper::default);
+                cat(reader, &mut sink, stripper.as_mut(), sample, max_events)?;
+            }
+
+            Format::Json => cat_json(reader, &mut sink, relative_timestamps, sample, max_events)?,
+            Format::TextSgr => cat_text_sgr(reader, &mut sink, sample, max_events)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates the output file, refusing to replace an existing one unless
+/// `overwrite` is set.
+pub fn create(path: &str, overwrite: bool) -> anyhow::Result<fs::File> {
+    if !overwrite && Path::new(path).exists() {
+        bail!("{} already exists, use --overwrite to replace it", path);
+    }
+
+    Ok(fs::File::create(path)?)
+}
+
+fn cat<R: io::BufRead, W: Write>(
+    reader: R,
+    sink: &mut W,
+    mut stripper: Option<&mut ansi::Stripper>,
+    sample: Option<Sample>,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    let (_header, events) = asciicast::open(reader)?;
+
+    let events = asciicast::limit(events, max_events)
+        .filter(|e| e.as_ref().map_or(true, |e| e.code == EventCode::Output));
+
+    for event in sample_events(events, sample) {
+        let event = event?;
+
+        match &mut stripper {
+            Some(stripper) => sink.write_all(&stripper.strip(event.data.as_bytes()))?,
+            None => sink.write_all(event.data.as_bytes())?,
+        }
+    }
+
+    sink.flush()?;
+
+    Ok(())
+}
+
+/// Prints the output the way it ended up displayed, with cursor movement
+/// (e.g. of progress bars) replayed rather than passed through.
+fn cat_text_sgr<R: io::BufRead, W: Write>(
+    reader: R,
+    sink: &mut W,
+    sample: Option<Sample>,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    let (header, events) = asciicast::open(reader)?;
+
+    let events = asciicast::limit(events, max_events).filter(|e| {
+        e.as_ref().map_or(true, |e| {
+            matches!(e.code, EventCode::Output | EventCode::Resize)
+        })
+    });
+
+    let terminal = render::replay(
+        (header.cols, header.rows),
+        sample_events(events, sample),
+        None,
+    )?;
+    sink.write_all(terminal.full_ansi().as_bytes())?;
+    sink.flush()?;
+
+    Ok(())
+}
+
+fn cat_json<R: io::BufRead, W: Write>(
+    reader: R,
+    sink: &mut W,
+    relative_timestamps: bool,
+    sample: Option<Sample>,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    let (_header, events) = asciicast::open(reader)?;
+    let mut prev_time = 0.0;
+
+    for event in sample_events(asciicast::limit(events, max_events), sample) {
+        let mut event = event?;
+        let time = event.time;
+
+        // rounded to microseconds, like the recorded times
+        if relative_timestamps {
+            event.time = ((time - prev_time) * 1_000_000.0).round() / 1_000_000.0;
+        }
+
+        prev_time = time;
+        serde_json::to_writer(&mut *sink, &event)?;
+        sink.write_all(b"\n")?;
+    }
+
+    sink.flush()?;
+
+    Ok(())
+}
+
+/// The first or the last events, or all of them without `sample`. The last
+/// ones are only known after reading everything, so they're kept in memory
+/// in the meantime.
+fn sample_events<'a, I>(
+    events: I,
+    sample: Option<Sample>,
+) -> Box<dyn Iterator<Item = anyhow::Result<Event>> + 'a>
+where
+    I: Iterator<Item = anyhow::Result<Event>> + 'a,
+{
+    match sample {
+        None => Box::new(events),
+
+        Some(Sample::Head(n)) => Box::new(events.take(n)),
+
+        Some(Sample::Tail(n)) => {
+            let mut tail = VecDeque::with_capacity(n);
+
+            for event in events {
+                match event {
+                    Ok(event) => {
+                        if tail.len() == n {
+                            tail.pop_front();
+                        }
+
+                        if n > 0 {
+                            tail.push_back(event);
+                        }
+                    }
+
+                    Err(e) => return Box::new(iter::once(Err(e))),
+                }
+            }
+
+            Box::new(tail.into_iter().map(Ok))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Sample};
+    use crate::format::ansi::Stripper;
+    use std::fs;
+
+    #[test]
+    fn cat() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"o\", \"foo\"]\n\
+[2.0, \"i\", \"x\"]\n\
+[3.0, \"o\", \"bar\"]\n";
+
+        let mut output = Vec::new();
+        super::cat(&data[..], &mut output, None, None, None).unwrap();
+
+        assert_eq!(output, b"foobar");
+    }
+
+    #[test]
+    fn sample() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"o\", \"a\"]\n\
+[2.0, \"i\", \"x\"]\n\
+[3.0, \"o\", \"b\"]\n\
+[4.0, \"o\", \"c\"]\n";
+
+        let cat = |sample| {
+            let mut output = Vec::new();
+            super::cat(&data[..], &mut output, None, Some(sample), None).unwrap();
+
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(cat(Sample::Head(2)), "ab");
+        assert_eq!(cat(Sample::Head(5)), "abc");
+        assert_eq!(cat(Sample::Tail(2)), "bc");
+        assert_eq!(cat(Sample::Tail(5)), "abc");
+        assert_eq!(cat(Sample::Tail(0)), "");
+
+        // all events count when printing JSON
+        let mut output = Vec::new();
+        super::cat_json(&data[..], &mut output, false, Some(Sample::Tail(3)), None).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[2.0,\"i\",\"x\"]\n[3.0,\"o\",\"b\"]\n[4.0,\"o\",\"c\"]\n"
+        );
+    }
+
+    #[test]
+    fn strip_escapes() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"o\", \"\\u001b[32mok\\u001b[0\"]\n\
+[2.0, \"o\", \"m\\r\\n\\u001b[1A\\u001b[2Kdone\\r\\n\"]\n";
+
+        let mut output = Vec::new();
+        let mut stripper = Stripper::default();
+        super::cat(&data[..], &mut output, Some(&mut stripper), None, None).unwrap();
+
+        assert_eq!(output, b"ok\r\ndone\r\n");
+    }
+
+    #[test]
+    fn json() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"o\", \"foo\"]\n\
+[1.5, \"m\", \"\", \"intro\"]\n\
+[3.0, \"x-custom\", \"bar\"]\n";
+
+        let mut output = Vec::new();
+        super::cat_json(&data[..], &mut output, false, None, None).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[1.0,\"o\",\"foo\"]\n[1.5,\"m\",\"\",\"intro\"]\n[3.0,\"x-custom\",\"bar\"]\n"
+        );
+
+        let mut output = Vec::new();
+        super::cat_json(&data[..], &mut output, true, None, None).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[1.0,\"o\",\"foo\"]\n[0.5,\"m\",\"\",\"intro\"]\n[1.5,\"x-custom\",\"bar\"]\n"
+        );
+    }
+
+    #[test]
+    fn text_sgr() {
+        let data = b"{\"version\": 2, \"width\": 10, \"height\": 2}\n\
+[1.0, \"o\", \"50%\"]\n\
+[2.0, \"i\", \"x\"]\n\
+[3.0, \"o\", \"\\r\\u001b[1mdone\\u001b[0m\\r\\n\"]\n";
+
+        let mut output = Vec::new();
+        super::cat_text_sgr(&data[..], &mut output, None, None).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\x1b[0;1mdone\x1b[0m\n");
+    }
+
+    #[test]
+    fn output_file() {
+        let dir = crate::test_util::TempDir::new();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+
+        let header = "{\"version\": 2, \"width\": 80, \"height\": 24}\n";
+        fs::write(
+            path("1.cast"),
+            format!("{}[1.0, \"o\", \"foo\\r\\n\"]\n", header),
+        )
+        .unwrap();
+        fs::write(
+            path("2.cast"),
+            format!("{}[1.0, \"o\", \"bar\\r\\n\"]\n", header),
+        )
+        .unwrap();
+        let inputs = [path("1.cast"), path("2.cast")];
+
+        super::run(
+            &inputs,
+            Some(&path("out.txt")),
+            false,
+            false,
+            false,
+            Format::Raw,
+            false,
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(path("out.txt")).unwrap(),
+            "foo\r\nbar\r\n"
+        );
+
+        // existing file is kept unless asked to overwrite it
+        assert!(super::run(
+            &inputs[..1],
+            Some(&path("out.txt")),
+            false,
+            false,
+            false,
+            Format::Raw,
+            false,
+            None,
+            None
+        )
+        .is_err());
+        assert_eq!(
+            fs::read_to_string(path("out.txt")).unwrap(),
+            "foo\r\nbar\r\n"
+        );
+
+        super::run(
+            &inputs[..1],
+            Some(&path("out.txt")),
+            true,
+            false,
+            false,
+            Format::Raw,
+            false,
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(path("out.txt")).unwrap(), "foo\r\n");
+    }
+}
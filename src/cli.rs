@@ -0,0 +1,1069 @@
+use crate::format::asciicast;
+use crate::{cat, config, convert, edit, ls, markers, player, pty, rec, snapshot};
+use anyhow::{bail, Result};
+use clap::parser::ValueSource;
+use clap::{ArgGroup, ArgMatches, Args, CommandFactory, Parser, Subcommand};
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+#[command(name = "asciinema")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Refuse to process recordings with more than given number of events
+    #[arg(long, global = true, value_name = "N")]
+    max_events: Option<usize>,
+}
+
+#[derive(Debug, Subcommand)]
+// parsed once per run, so the size of `rec` options doesn't matter
+#[allow(clippy::large_enum_variant)]
+enum Commands {
+    /// Record terminal session
+    ///
+    /// Exits with the recorded command's exit status, or 125 if recording
+    /// itself fails.
+    #[command(name = "rec")]
+    Record(RecordArgs),
+
+    /// Play terminal session
+    ///
+    /// Keyboard shortcuts: space - toggle pause, . - step a frame (when paused),
+    /// ] - skip to the next marker (when paused), + / - - speed up / slow down,
+    /// 1-5 - speed presets (1x, 1.5x, 2x, 3x, 5x), 0 - reset speed to 1x,
+    /// ctrl+c - exit.
+    Play {
+        /// Recording to play, `-` to read it from stdin
+        filename: String,
+
+        /// Limit idle time to given number of seconds, overriding the limit
+        /// saved in the recording
+        ///
+        /// The limit applies to the recording's time, before adjusting for
+        /// playback speed, e.g. with 2 second limit and 2x speed idle time gets
+        /// limited to 1 second.
+        #[arg(short, long, value_name = "SECS", value_parser = parse_idle_time_limit::<f64>)]
+        idle_time_limit: Option<f64>,
+
+        /// Set playback speed, e.g. 2 for double speed or 0.5 for slow motion
+        #[arg(short, long, value_parser = parse_speed)]
+        speed: Option<f64>,
+
+        /// Replay raw recordings as if output was printed at given number of
+        /// bytes per second, instead of dumping it all at once
+        #[arg(long, value_name = "BYTES_PER_SEC", value_parser = parse_rate)]
+        assume_rate: Option<f64>,
+
+        /// Loop loop loop loop
+        #[arg(short, long, name = "loop")]
+        loop_: bool,
+
+        /// Pause for given number of seconds before starting over when
+        /// looping
+        #[arg(long, value_name = "SECS", requires = "loop", value_parser = parse_delay, default_value_t = 0.0)]
+        loop_delay: f64,
+
+        /// Clear the screen before starting over when looping
+        #[arg(long, requires = "loop")]
+        loop_clear: bool,
+
+        /// Automatically pause on markers
+        #[arg(short = 'm', long)]
+        pause_on_markers: bool,
+
+        /// Start playback at given point of the recording (output before it is
+        /// printed right away, to get the screen right)
+        #[arg(long, value_name = "SECS", value_parser = parse_time)]
+        start_at: Option<f64>,
+
+        /// Stop playback at given point of the recording
+        #[arg(long, value_name = "SECS", value_parser = parse_time)]
+        end_at: Option<f64>,
+
+        /// Quiet mode - don't show playback progress on stderr
+        ///
+        /// Progress is only shown when stderr is a terminal different from the
+        /// one the recording is played in, e.g. `2>/dev/pts/3`.
+        #[arg(short, long)]
+        quiet: bool,
+    },
+
+    /// Print full output of terminal sessions
+    Cat {
+        /// Recordings to print, `-` to read one from stdin
+        #[arg(required = true)]
+        filename: Vec<String>,
+
+        /// Clear the screen before printing
+        #[arg(long)]
+        reset: bool,
+
+        /// Print plain text, without colors, cursor movement and other escape
+        /// sequences
+        #[arg(long, conflicts_with = "reset")]
+        strip_escapes: bool,
+
+        /// Write to given file instead of stdout ("-" for stdout)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long, requires = "output")]
+        overwrite: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = cat::Format::Raw, conflicts_with_all = ["reset", "strip_escapes"])]
+        format: cat::Format,
+
+        /// With --format json, give each event's time relative to the previous
+        /// event instead of to the start of the recording
+        #[arg(long)]
+        relative_timestamps: bool,
+
+        /// Print only the first N events (not lines) of each recording
+        #[arg(long, value_name = "N", conflicts_with = "tail")]
+        head: Option<usize>,
+
+        /// Print only the last N events (not lines) of each recording
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+    },
+
+    /// Convert recording to another format
+    ///
+    /// Output format is chosen by the file extension: .cast for asciicast v2,
+    /// .json for asciicast v1, .raw or .txt for raw output.
+    Convert {
+        input: String,
+        output: String,
+
+        /// Output format, overriding the one implied by the extension
+        #[arg(short, long, value_enum)]
+        format: Option<convert::Format>,
+
+        /// Assume raw output was printed at given number of bytes per second,
+        /// allowing conversion of raw recordings to asciicast
+        #[arg(long, value_name = "BYTES_PER_SEC", value_parser = parse_rate)]
+        assume_rate: Option<f64>,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Rewrite timing of a recording, saving the result as a new one
+    ///
+    /// Idle time is limited the same way as on playback, so the result plays
+    /// the same without the limit.
+    Edit {
+        input: String,
+        output: String,
+
+        /// Limit idle time to given number of seconds
+        #[arg(short, long, value_name = "SECS", value_parser = parse_idle_time_limit::<f64>)]
+        idle_time_limit: Option<f64>,
+
+        /// Speed the recording up by given factor, e.g. 2 for double speed
+        /// or 0.5 for slow motion
+        ///
+        /// The idle time limit applies before speeding up, e.g. with 2 second
+        /// limit and 2x speed idle time gets limited to 1 second.
+        #[arg(short, long, value_parser = parse_speed, default_value_t = 1.0)]
+        speed: f64,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Check asciicast file for structural problems
+    #[command(alias = "verify")]
+    Lint { filename: String },
+
+    /// List markers in a recording, with their time and label
+    Markers {
+        filename: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = markers::Format::Text)]
+        format: markers::Format,
+    },
+
+    /// Print the screen of a recording at given point, as a terminal would
+    /// display it
+    Snapshot {
+        /// Recording to render, `-` to read it from stdin
+        filename: String,
+
+        /// Point of the recording to render, its end by default
+        #[arg(long, value_name = "SECS", value_parser = parse_time)]
+        at: Option<f64>,
+
+        /// Keep colors and text attributes, as SGR escape sequences
+        #[arg(long)]
+        ansi: bool,
+
+        /// Write to given file instead of stdout ("-" for stdout)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long, requires = "output")]
+        overwrite: bool,
+    },
+
+    /// List recordings in a directory
+    ///
+    /// Only headers get read, plus the end of uncompressed asciicast v2
+    /// recordings for their duration, which isn't known for other ones.
+    Ls {
+        /// Directory to scan
+        #[arg(default_value = ".")]
+        dir: String,
+
+        /// Print the listing as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Sort recordings by given field
+        #[arg(long, value_enum, default_value_t = ls::Sort::Name)]
+        sort: ls::Sort,
+    },
+
+    /// Upload recording to asciinema.org
+    Upload {
+        /// Filename/path of asciicast to upload
+        filename: String,
+    },
+
+    /// Link this system to asciinema.org account
+    Auth {
+        /// URL of the asciinema server, e.g. a self-hosted one [default:
+        /// $ASCIINEMA_API_URL or https://asciinema.org]
+        #[arg(long, value_name = "URL", value_parser = parse_server_url)]
+        server_url: Option<String>,
+    },
+}
+
+/// Options of `rec`.
+#[derive(Debug, Args)]
+#[command(group = ArgGroup::new("input").args(["stdin", "input_only"]).multiple(true))]
+pub struct RecordArgs {
+    #[arg(required_unless_present = "output_command")]
+    pub filename: Option<String>,
+
+    /// Pipe the recording to given shell command instead of saving it to a
+    /// file, e.g. "gzip > demo.cast.gz"
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["filename", "append", "overwrite", "no_follow_symlinks", "write_meta"])]
+    pub output_command: Option<String>,
+
+    /// Enable input recording
+    ///
+    /// Input gets to the recorded program the same with or without this,
+    /// it's only about saving it in the recording.
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Don't record input typed while the terminal doesn't echo it (e.g. passwords)
+    ///
+    /// This is detected from the terminal mode at the time the input is
+    /// read, so keys typed ahead of a prompt may still be recorded, and
+    /// programs hiding input in other ways (e.g. raw mode) aren't detected.
+    #[arg(long, requires = "input")]
+    pub no_echo_input: bool,
+
+    /// Also record names of the pressed keys (e.g. C-c, Enter, Up) as "k"
+    /// events next to the raw input
+    #[arg(long, requires = "input")]
+    pub input_names: bool,
+
+    /// Record input only, leaving output out (implies --stdin)
+    ///
+    /// With --raw the file contains just the typed keystrokes, ready to be
+    /// replayed into another session.
+    #[arg(long)]
+    pub input_only: bool,
+
+    /// Append to existing asciicast file
+    #[arg(long)]
+    pub append: bool,
+
+    /// Save raw output only
+    #[arg(long, overrides_with = "no_raw")]
+    pub raw: bool,
+
+    /// Save asciicast even when the config file sets raw
+    #[arg(long, overrides_with = "raw")]
+    pub no_raw: bool,
+
+    /// Start the recording with clearing the screen (fresh recordings
+    /// only), so that it's played on a blank one
+    #[arg(long, alias = "reset")]
+    pub cls: bool,
+
+    /// Overwrite target file if it already exists
+    #[arg(long, conflicts_with = "append")]
+    pub overwrite: bool,
+
+    /// Fail instead of writing through when target file is a symlink
+    ///
+    /// By default a symlink at the target path is followed and the
+    /// recording is written to the file it points to.
+    #[arg(long)]
+    pub no_follow_symlinks: bool,
+
+    /// Record to a temporary file next to the target, moving it in place
+    /// once the recording is complete
+    ///
+    /// This way there's either a complete recording at the target path or
+    /// none, even if asciinema gets killed or crashes.
+    #[arg(long, conflicts_with_all = ["append", "output_command"])]
+    pub tmp: bool,
+
+    /// Command to record [default: $SHELL]
+    ///
+    /// Given more than once, the commands are recorded one after another,
+    /// until one of them fails (see --keep-going).
+    #[arg(short, long)]
+    pub command: Vec<String>,
+
+    /// Shell running the command, with the option making it take the
+    /// command as an argument, e.g. "bash -c" [default: /bin/sh -c]
+    ///
+    /// It's split on whitespace, quoting isn't supported.
+    #[arg(long, value_name = "SHELL", value_parser = parse_exec_shell, conflicts_with = "fd")]
+    pub exec_shell: Option<String>,
+
+    /// Go on with the next --command when one fails, the exit status is
+    /// still the one of the first failure
+    #[arg(long, requires = "command")]
+    pub keep_going: bool,
+
+    /// Add a marker labelled with the command between --commands
+    #[arg(long, requires = "command")]
+    pub mark_commands: bool,
+
+    /// Record output read from given file descriptor (e.g. a fifo or a pty of
+    /// another process) instead of running a command
+    ///
+    /// Recording ends when the descriptor gets closed. Neither input nor
+    /// terminal resizes can be recorded in this mode.
+    #[arg(long, value_name = "N", conflicts_with_all = ["command", "stdin", "input_only", "record_exit"])]
+    pub fd: Option<i32>,
+
+    /// List of env vars to save (may use * wildcards, '*' saves all)
+    /// [default: SHELL,TERM,LANG]
+    #[arg(short, long)]
+    pub env: Option<String>,
+
+    /// File with list of env vars to save, one per line (may use * wildcards)
+    #[arg(long, value_name = "PATH")]
+    pub env_file: Option<String>,
+
+    /// Save vars looking like secrets (*TOKEN*, *SECRET*, *PASSWORD*, *KEY*)
+    /// when they're matched by a wildcard
+    #[arg(long)]
+    pub no_redact: bool,
+
+    /// Save the working directory of the recorded command in the recording
+    #[arg(long)]
+    pub record_cwd: bool,
+
+    /// Title of the recording, defaults to the recorded command (empty
+    /// title means no title)
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Colors for players to show the recording with: a theme name
+    /// (asciinema, tango, solarized-dark, solarized-light) or
+    /// FG:BG:PALETTE, all as #rrggbb, with 8 or 16 palette colors
+    #[arg(long, value_name = "THEME")]
+    pub theme: Option<asciicast::Theme>,
+
+    /// Limit idle time to given number of seconds, 0 removes it entirely
+    ///
+    /// The limit is saved in the recording's header, and applied by players
+    /// on playback. The recording itself keeps the original timing.
+    #[arg(short, long, value_name = "SECS", value_parser = parse_idle_time_limit::<f32>)]
+    pub idle_time_limit: Option<f32>,
+
+    /// Override terminal width (columns) for recorded command, auto follows
+    /// the terminal
+    ///
+    /// Either of --cols and --rows set to a number also allows recording
+    /// without a terminal.
+    #[arg(long, value_name = "COLS")]
+    pub cols: Option<pty::Dimension>,
+
+    /// Override terminal height (rows) for recorded command, auto follows
+    /// the terminal
+    #[arg(long, value_name = "ROWS")]
+    pub rows: Option<pty::Dimension>,
+
+    /// Set TERM of the recorded command to xterm-256color, for recordings
+    /// which replay well in any terminal
+    ///
+    /// The original TERM gets saved in the header as "original_term".
+    #[arg(long)]
+    pub normalize_term: bool,
+
+    /// Quiet mode - suppress all notices/warnings
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Record even if the locale's character encoding is not ASCII or UTF-8
+    #[arg(long)]
+    pub force: bool,
+
+    /// Merge output chunks arriving within given number of milliseconds into one event
+    #[arg(long, value_name = "MS", default_value_t = 5)]
+    pub batch_time: u64,
+
+    /// If the recording ends in the alternate screen (e.g. a full-screen
+    /// program got cut off), switch back to the primary one at the end
+    #[arg(long)]
+    pub fix_alt_screen: bool,
+
+    /// Stop recording after given number of seconds, terminating the
+    /// recorded command
+    #[arg(long, value_name = "SECS", value_parser = parse_max_time, conflicts_with = "fd")]
+    pub max_time: Option<f64>,
+
+    /// Size of the buffer for reading the recorded program's output, in
+    /// bytes (1024 - 16777216). Bigger ones may keep up better with
+    /// programs printing a lot, at the cost of memory
+    #[arg(long, value_name = "BYTES", value_parser = parse_buffer_size, default_value_t = pty::DEFAULT_BUF_SIZE)]
+    pub buffer_size: usize,
+
+    /// Merge output into evenly spaced events, given number per second,
+    /// e.g. for exporting to video
+    #[arg(long, value_name = "HZ", value_parser = parse_rate, conflicts_with = "batch_time")]
+    pub constant_rate: Option<f64>,
+
+    /// Print summary of the recording as JSON to stderr when done
+    #[arg(long)]
+    pub json_summary: bool,
+
+    /// Record exit status of the command as the final ("x") event
+    #[arg(long)]
+    pub record_exit: bool,
+
+    /// Write recording metadata to <FILENAME>.meta.json
+    ///
+    /// Not available with --append, as it'd describe the appended part only.
+    #[arg(long, conflicts_with = "append")]
+    pub write_meta: bool,
+
+    /// Also write asciicast events to stdout as they're recorded, one JSON
+    /// line each, for processing the session live
+    ///
+    /// The stream always starts with a header, so it doesn't go with
+    /// --append, which leaves the header out.
+    #[arg(long, conflicts_with = "append")]
+    pub stream: bool,
+
+    /// Also serve asciicast events live to clients of a Unix socket
+    /// created at <PATH>, e.g. for watching the session from elsewhere
+    ///
+    /// Every client gets the header first, then events recorded since it
+    /// connected. Clients which can't keep up get disconnected.
+    #[arg(long, value_name = "PATH", conflicts_with = "append")]
+    pub socket: Option<String>,
+
+    /// Print what would be recorded and how, then exit without recording
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Refuse to record inside another asciinema recording, instead of
+    /// just warning about it
+    #[arg(long)]
+    pub no_nested: bool,
+
+    /// Hotkey for adding a marker, e.g. C-x. Keys typed after it, up to
+    /// <enter>, become the marker's label
+    #[arg(long, value_name = "KEY")]
+    pub marker_key: Option<String>,
+
+    /// Hotkey for pausing and resuming the recording, e.g. C-p. What
+    /// happens meanwhile is shown but not recorded, nor is the time it takes
+    #[arg(long, value_name = "KEY", alias = "pause-hotkey")]
+    pub pause_key: Option<String>,
+}
+
+const DEFAULT_SERVER_URL: &str = "https://asciinema.org";
+
+/// Exit status for asciinema's own failures during `rec`, chosen so it can
+/// be told apart from the recorded command's status (same as env(1) uses).
+const REC_ERROR_STATUS: u8 = 125;
+
+const MIN_BUF_SIZE: usize = 1024;
+
+const MAX_BUF_SIZE: usize = 16 * 1024 * 1024;
+
+/// Entry point of the `asciinema` binary.
+pub fn main() -> ExitCode {
+    let argv = match with_config(env::args_os().collect()) {
+        Ok(argv) => argv,
+
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return ExitCode::from(REC_ERROR_STATUS);
+        }
+    };
+
+    let cli = Cli::parse_from(argv);
+
+    let error_status = match cli.command {
+        Commands::Record(_) => REC_ERROR_STATUS,
+        _ => 1,
+    };
+
+    match run(cli) {
+        Ok(status) => ExitCode::from(status),
+
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(error_status)
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<u8> {
+    let mut status = 0;
+
+    match cli.command {
+        Commands::Record(args) => status = rec::run(args)?,
+
+        Commands::Play {
+            filename,
+            idle_time_limit,
+            speed,
+            assume_rate,
+            loop_,
+            loop_delay,
+            loop_clear,
+            pause_on_markers,
+            start_at,
+            end_at,
+            quiet,
+        } => {
+            if let (Some(start_at), Some(end_at)) = (start_at, end_at) {
+                if end_at <= start_at {
+                    bail!("--end-at must be later than --start-at");
+                }
+            }
+
+            let loop_ = loop_.then(|| player::Loop {
+                delay: Duration::from_secs_f64(loop_delay),
+                clear: loop_clear,
+            });
+
+            player::Player {
+                idle_time_limit,
+                speed: speed.unwrap_or(1.0),
+                assume_rate,
+                loop_,
+                pause_on_markers,
+                quiet,
+                max_events: cli.max_events,
+                start_at,
+                end_at,
+            }
+            .play(&filename)?;
+        }
+
+        Commands::Cat {
+            filename,
+            reset,
+            strip_escapes,
+            output,
+            overwrite,
+            format,
+            relative_timestamps,
+            head,
+            tail,
+        } => {
+            if relative_timestamps && format != cat::Format::Json {
+                bail!("--relative-timestamps works with --format json only");
+            }
+
+            cat::run(
+                &filename,
+                output.as_deref(),
+                overwrite,
+                reset,
+                strip_escapes,
+                format,
+                relative_timestamps,
+                head.map(cat::Sample::Head).or(tail.map(cat::Sample::Tail)),
+                cli.max_events,
+            )?
+        }
+
+        Commands::Convert {
+            input,
+            output,
+            format,
+            assume_rate,
+            overwrite,
+        } => convert::run(
+            &input,
+            &output,
+            format,
+            assume_rate,
+            overwrite,
+            cli.max_events,
+        )?,
+
+        Commands::Edit {
+            input,
+            output,
+            idle_time_limit,
+            speed,
+            overwrite,
+        } => edit::run(
+            &input,
+            &output,
+            idle_time_limit,
+            speed,
+            overwrite,
+            cli.max_events,
+        )?,
+
+        Commands::Lint { filename } => {
+            let file = fs::File::open(&filename)?;
+            let problems = asciicast::lint(io::BufReader::new(file), cli.max_events)?;
+
+            for problem in &problems {
+                println!("{}: {}", filename, problem);
+            }
+
+            if !problems.is_empty() {
+                bail!("found {} problem(s) in {}", problems.len(), filename);
+            }
+        }
+
+        Commands::Markers { filename, format } => markers::run(&filename, format, cli.max_events)?,
+
+        Commands::Snapshot {
+            filename,
+            at,
+            ansi,
+            output,
+            overwrite,
+        } => snapshot::run(
+            &filename,
+            at,
+            ansi,
+            output.as_deref(),
+            overwrite,
+            cli.max_events,
+        )?,
+
+        Commands::Ls { dir, json, sort } => ls::run(dir, json, sort)?,
+
+        Commands::Upload { .. } => todo!(),
+
+        Commands::Auth { server_url } => {
+            let server_url = server_url_or_default(server_url, env::var("ASCIINEMA_API_URL").ok());
+
+            println!("Open the following URL in a web browser to link your install ID with your {} user account:\n", server_url);
+            println!(
+                "{}/connect/{}\n",
+                server_url.trim_end_matches('/'),
+                config::install_id()?
+            );
+            println!("This will associate all recordings uploaded from this machine (past and future ones) to your account, and allow you to manage them (change title/theme, delete) at {}.", server_url);
+        }
+    }
+
+    Ok(status)
+}
+
+/// Adds `rec` options from the config file to the command line, when it's
+/// `rec` that's run.
+fn with_config(argv: Vec<OsString>) -> Result<Vec<OsString>> {
+    // errors get reported by the actual parsing, with the config options in
+    let Ok(matches) = Cli::command()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)
+    else {
+        return Ok(argv);
+    };
+
+    match matches.subcommand() {
+        Some(("rec", given)) => Ok(apply_config(argv, given, config::load()?.record)),
+        _ => Ok(argv),
+    }
+}
+
+/// Adds `rec` options not given on the command line from the config file,
+/// so that they get parsed, and validated, the same way as the given ones.
+fn apply_config(
+    mut argv: Vec<OsString>,
+    given: &ArgMatches,
+    config: config::Record,
+) -> Vec<OsString> {
+    let given = |id| given.value_source(id) == Some(ValueSource::CommandLine);
+    let mut config_args = Vec::new();
+
+    // a command makes no sense when attaching to a descriptor
+    if let Some(command) = config.command.filter(|_| !given("fd") && !given("command")) {
+        config_args.push(format!("--command={}", command));
+    }
+
+    if let Some(env) = config.env.filter(|_| !given("env")) {
+        config_args.push(format!("--env={}", env));
+    }
+
+    if let Some(limit) = config.idle_time_limit.filter(|_| !given("idle_time_limit")) {
+        config_args.push(format!("--idle-time-limit={}", limit));
+    }
+
+    if config.raw && !given("raw") && !given("no_raw") {
+        config_args.push("--raw".to_owned());
+    }
+
+    if let Some(key) = config.marker_key.filter(|_| !given("marker_key")) {
+        config_args.push(format!("--marker-key={}", key));
+    }
+
+    if let Some(key) = config.pause_key.filter(|_| !given("pause_key")) {
+        config_args.push(format!("--pause-key={}", key));
+    }
+
+    // right after the subcommand, where they can't be taken for a filename
+    let at = argv
+        .iter()
+        .skip(1)
+        .position(|arg| arg == "rec")
+        .map_or(argv.len(), |i| i + 2);
+    argv.splice(at..at, config_args.into_iter().map(OsString::from));
+
+    argv
+}
+
+/// Server URL given with --server-url, falling back to $ASCIINEMA_API_URL
+/// and then to asciinema.org.
+fn server_url_or_default(flag: Option<String>, env_var: Option<String>) -> String {
+    flag.or(env_var).unwrap_or(DEFAULT_SERVER_URL.to_owned())
+}
+
+fn parse_server_url(s: &str) -> Result<String, String> {
+    match s.split_once("://") {
+        Some((scheme, host)) if !scheme.is_empty() && !host.is_empty() => Ok(s.to_owned()),
+        _ => Err("server URL must include a scheme, e.g. https://".to_owned()),
+    }
+}
+
+fn parse_buffer_size(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(size) if (MIN_BUF_SIZE..=MAX_BUF_SIZE).contains(&size) => Ok(size),
+        Ok(_) => Err(format!(
+            "buffer size must be between {} and {} bytes",
+            MIN_BUF_SIZE, MAX_BUF_SIZE
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        Ok(_) => Err("speed must be a positive number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_exec_shell(s: &str) -> Result<String, String> {
+    if s.trim().is_empty() {
+        return Err("shell can't be empty".to_owned());
+    }
+
+    Ok(s.to_owned())
+}
+
+fn parse_idle_time_limit<T>(s: &str) -> Result<T, String>
+where
+    T: FromStr + Into<f64> + Copy,
+    T::Err: fmt::Display,
+{
+    match s.parse::<T>() {
+        Ok(limit) if limit.into() >= 0.0 && limit.into().is_finite() => Ok(limit),
+        Ok(_) => Err("idle time limit must be a non-negative number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_delay(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(delay) if delay >= 0.0 && delay.is_finite() => Ok(delay),
+        Ok(_) => Err("delay must be a non-negative number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_time(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(time) if time >= 0.0 && time.is_finite() => Ok(time),
+        Ok(_) => Err("time must be a non-negative number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_max_time(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(secs),
+        Ok(_) => Err("max time must be a positive number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        Ok(_) => Err("rate must be a positive number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn parse_speed() {
+        use super::parse_speed;
+
+        assert_eq!(parse_speed("2"), Ok(2.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0").is_err());
+        assert!(parse_speed("-1").is_err());
+        assert!(parse_speed("inf").is_err());
+        assert!(parse_speed("NaN").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn server_url() {
+        use super::{parse_server_url, server_url_or_default};
+
+        let some = |url: &str| Some(url.to_owned());
+
+        assert_eq!(
+            server_url_or_default(some("http://flag"), some("http://env")),
+            "http://flag"
+        );
+        assert_eq!(
+            server_url_or_default(None, some("http://env")),
+            "http://env"
+        );
+        assert_eq!(server_url_or_default(None, None), "https://asciinema.org");
+
+        assert!(parse_server_url("https://asciinema.example.com").is_ok());
+        assert!(parse_server_url("asciinema.example.com").is_err());
+        assert!(parse_server_url("://asciinema.example.com").is_err());
+        assert!(parse_server_url("https://").is_err());
+    }
+
+    #[test]
+    fn parse_buffer_size() {
+        use super::parse_buffer_size;
+
+        assert_eq!(parse_buffer_size("1024"), Ok(1024));
+        assert_eq!(parse_buffer_size("16777216"), Ok(16 * 1024 * 1024));
+        assert!(parse_buffer_size("1023").is_err());
+        assert!(parse_buffer_size("16777217").is_err());
+        assert!(parse_buffer_size("-1").is_err());
+        assert!(parse_buffer_size("1k").is_err());
+    }
+
+    #[test]
+    fn apply_config() {
+        use super::{Cli, Commands, RecordArgs};
+        use clap::{CommandFactory, Parser};
+        use std::ffi::OsString;
+
+        let config = || crate::config::Record {
+            command: Some("bash -l".to_owned()),
+            env: Some("SHELL,TERM,USER".to_owned()),
+            idle_time_limit: Some(2.0),
+            raw: true,
+            marker_key: Some("C-x".to_owned()),
+            pause_key: Some("C-p".to_owned()),
+        };
+
+        let apply = |args: &[&str], config: crate::config::Record| {
+            let argv = [&["asciinema", "rec"], args]
+                .concat()
+                .into_iter()
+                .map(OsString::from)
+                .collect::<Vec<_>>();
+
+            let matches = Cli::command().ignore_errors(true).get_matches_from(&argv);
+            let argv =
+                super::apply_config(argv, matches.subcommand_matches("rec").unwrap(), config);
+
+            Cli::try_parse_from(argv).map(|cli| match cli.command {
+                Commands::Record(args) => args,
+                _ => unreachable!(),
+            })
+        };
+
+        let record = |args: &[&str]| apply(args, config()).unwrap();
+
+        let RecordArgs {
+            command,
+            env,
+            idle_time_limit,
+            raw,
+            marker_key,
+            pause_key,
+            ..
+        } = record(&["demo.cast"]);
+
+        assert_eq!(command, ["bash -l"]);
+        assert_eq!(env.as_deref(), Some("SHELL,TERM,USER"));
+        assert_eq!(idle_time_limit, Some(2.0));
+        assert!(raw);
+        assert_eq!(marker_key.as_deref(), Some("C-x"));
+        assert_eq!(pause_key.as_deref(), Some("C-p"));
+
+        let RecordArgs {
+            command,
+            env,
+            idle_time_limit,
+            raw,
+            marker_key,
+            pause_key,
+            ..
+        } = record(&[
+            "-c",
+            "vim",
+            "-e",
+            "SHELL",
+            "-i",
+            "0.5",
+            "--no-raw",
+            "--marker-key",
+            "C-b",
+            "--pause-hotkey",
+            "C-o",
+            "demo.cast",
+        ]);
+
+        assert_eq!(command, ["vim"]);
+        assert_eq!(env.as_deref(), Some("SHELL"));
+        assert_eq!(idle_time_limit, Some(0.5));
+        assert!(!raw);
+        assert_eq!(marker_key.as_deref(), Some("C-b"));
+        assert_eq!(pause_key.as_deref(), Some("C-o"));
+
+        let RecordArgs { command, .. } = record(&["--fd", "3", "demo.cast"]);
+
+        assert!(command.is_empty());
+
+        // options requiring a command are fine with the one from the config
+        assert!(record(&["--keep-going", "demo.cast"]).keep_going);
+
+        // values from the config get validated the same way as the given ones
+        let invalid = crate::config::Record {
+            idle_time_limit: Some(-1.0),
+            ..Default::default()
+        };
+
+        assert!(apply(&["demo.cast"], invalid).is_err());
+    }
+
+    #[test]
+    fn parse_idle_time_limit() {
+        use super::parse_idle_time_limit;
+
+        assert_eq!(parse_idle_time_limit::<f32>("0"), Ok(0.0));
+        assert_eq!(parse_idle_time_limit::<f64>("2.5"), Ok(2.5));
+        assert!(parse_idle_time_limit::<f32>("-1").is_err());
+        assert!(parse_idle_time_limit::<f64>("NaN").is_err());
+        assert!(parse_idle_time_limit::<f64>("inf").is_err());
+    }
+
+    #[test]
+    fn zero_size() {
+        use super::Cli;
+        use clap::Parser;
+
+        let parse = |args: &[&str]| Cli::try_parse_from([&["asciinema", "rec"], args].concat());
+
+        assert!(parse(&["--cols", "0", "demo.cast"]).is_err());
+        assert!(parse(&["--rows", "0", "demo.cast"]).is_err());
+        assert!(parse(&["--cols", "1", "--rows", "1", "demo.cast"]).is_ok());
+        assert!(parse(&["--cols", "100", "--rows", "auto", "demo.cast"]).is_ok());
+    }
+
+    #[test]
+    fn no_echo_input() {
+        use super::Cli;
+        use clap::Parser;
+
+        let parse = |args: &[&str]| Cli::try_parse_from([&["asciinema", "rec"], args].concat());
+
+        assert!(parse(&["--no-echo-input", "--stdin", "demo.cast"]).is_ok());
+        assert!(parse(&["--no-echo-input", "--input-only", "demo.cast"]).is_ok());
+        assert!(parse(&["--no-echo-input", "demo.cast"]).is_err());
+    }
+
+    #[test]
+    fn input_names() {
+        use super::Cli;
+        use clap::Parser;
+
+        let parse = |args: &[&str]| Cli::try_parse_from([&["asciinema", "rec"], args].concat());
+
+        assert!(parse(&["--input-names", "--stdin", "demo.cast"]).is_ok());
+        assert!(parse(&["--input-names", "--input-only", "demo.cast"]).is_ok());
+        assert!(parse(&["--input-names", "demo.cast"]).is_err());
+    }
+
+    #[test]
+    fn stream() {
+        use super::Cli;
+        use clap::Parser;
+
+        let parse = |args: &[&str]| Cli::try_parse_from([&["asciinema", "rec"], args].concat());
+
+        assert!(parse(&["--stream", "demo.cast"]).is_ok());
+        assert!(parse(&["--stream", "--append", "demo.cast"]).is_err());
+    }
+
+    #[test]
+    fn convert_assume_rate() {
+        use super::Cli;
+        use clap::Parser;
+
+        let parse = |rate: &str| {
+            Cli::try_parse_from(["asciinema", "convert", "--assume-rate", rate, "in", "out"])
+        };
+
+        assert!(parse("100").is_ok());
+        assert!(parse("0").is_err());
+        assert!(parse("NaN").is_err());
+    }
+
+    #[test]
+    fn write_meta() {
+        use super::Cli;
+        use clap::Parser;
+
+        let parse = |args: &[&str]| Cli::try_parse_from([&["asciinema", "rec"], args].concat());
+
+        assert!(parse(&["--write-meta", "demo.cast"]).is_ok());
+        assert!(parse(&["--write-meta", "--append", "demo.cast"]).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub record: Record,
+}
+
+/// Defaults for `rec` options, overridden by the ones given on the command
+/// line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Record {
+    pub command: Option<String>,
+    pub env: Option<String>,
+    pub idle_time_limit: Option<f32>,
+    pub raw: bool,
+    pub marker_key: Option<String>,
+    pub pause_key: Option<String>,
+}
+
+/// Directory for asciinema's configuration and state, which is
+/// `$ASCIINEMA_CONFIG_HOME`, or `asciinema` in `$XDG_CONFIG_HOME` or, when
+/// neither is set, in `~/.config`.
+pub fn dir() -> anyhow::Result<PathBuf> {
+    resolve_dir(|name| env::var_os(name))
+}
+
+/// Random ID identifying this installation to the server, generated and
+/// saved in the config directory on first use.
+pub fn install_id() -> anyhow::Result<String> {
+    read_or_create_install_id(&dir()?)
+}
+
+/// Loads `config.toml` from the config directory, a missing file being the
+/// same as an empty one.
+pub fn load() -> anyhow::Result<Config> {
+    match dir() {
+        Ok(dir) => load_file(dir.join("config.toml")),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+pub fn load_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Config> {
+    let path = path.as_ref();
+
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("can't read {}", path.display())),
+    };
+
+    toml::from_str(&data).with_context(|| format!("invalid config file {}", path.display()))
+}
+
+fn resolve_dir<F: Fn(&str) -> Option<OsString>>(var: F) -> anyhow::Result<PathBuf> {
+    let var = |name| var(name).filter(|value| !value.is_empty());
+
+    if let Some(dir) = var("ASCIINEMA_CONFIG_HOME") {
+        return Ok(dir.into());
+    }
+
+    if let Some(dir) = var("XDG_CONFIG_HOME") {
+        return Ok(PathBuf::from(dir).join("asciinema"));
+    }
+
+    let Some(home) = var("HOME") else {
+        bail!("can't find config directory, set ASCIINEMA_CONFIG_HOME or HOME");
+    };
+
+    Ok(PathBuf::from(home).join(".config").join("asciinema"))
+}
+
+fn read_or_create_install_id(dir: &Path) -> anyhow::Result<String> {
+    let path = dir.join("install-id");
+
+    match fs::read_to_string(&path) {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_owned()),
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e).with_context(|| format!("can't read {}", path.display())),
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&path, &id))
+        .with_context(|| format!("can't save install ID to {}", path.display()))?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn resolve_dir(vars: &[(&str, &str)]) -> Option<PathBuf> {
+        let vars: HashMap<_, _> = vars.iter().cloned().collect();
+
+        super::resolve_dir(|name| vars.get(name).map(OsString::from)).ok()
+    }
+
+    #[test]
+    fn resolve_dir_precedence() {
+        let home = ("HOME", "/home/alice");
+        let xdg = ("XDG_CONFIG_HOME", "/xdg");
+        let asciinema = ("ASCIINEMA_CONFIG_HOME", "/asciinema");
+
+        assert_eq!(
+            resolve_dir(&[home, xdg, asciinema]),
+            Some("/asciinema".into())
+        );
+        assert_eq!(resolve_dir(&[home, xdg]), Some("/xdg/asciinema".into()));
+        assert_eq!(
+            resolve_dir(&[home]),
+            Some("/home/alice/.config/asciinema".into())
+        );
+        assert_eq!(
+            resolve_dir(&[home, ("XDG_CONFIG_HOME", "")]),
+            Some("/home/alice/.config/asciinema".into())
+        );
+        assert_eq!(resolve_dir(&[]), None);
+    }
+
+    #[test]
+    fn load_file() {
+        let dir = TempDir::new();
+        let path = dir.join("config.toml");
+
+        let config = super::load_file(&path).unwrap();
+
+        assert_eq!(config.record.command, None);
+        assert!(!config.record.raw);
+
+        fs::write(
+            &path,
+            "[record]\ncommand = \"bash -l\"\nenv = \"SHELL,TERM,USER\"\nidle_time_limit = 2.5\nraw = true\n",
+        )
+        .unwrap();
+
+        let config = super::load_file(&path).unwrap();
+
+        assert_eq!(config.record.command.as_deref(), Some("bash -l"));
+        assert_eq!(config.record.env.as_deref(), Some("SHELL,TERM,USER"));
+        assert_eq!(config.record.idle_time_limit, Some(2.5));
+        assert!(config.record.raw);
+
+        fs::write(&path, "[record]\nstdin = true\n").unwrap();
+
+        assert!(super::load_file(&path).is_err());
+    }
+
+    #[test]
+    fn install_id() {
+        let home = TempDir::new();
+        let dir = resolve_dir(&[("HOME", home.to_str().unwrap())]).unwrap();
+
+        let id = super::read_or_create_install_id(&dir).unwrap();
+
+        assert_eq!(id.len(), 36);
+        assert_eq!(fs::read_to_string(dir.join("install-id")).unwrap(), id);
+        assert_eq!(super::read_or_create_install_id(&dir).unwrap(), id);
+
+        fs::write(dir.join("install-id"), "abc\n").unwrap();
+
+        assert_eq!(super::read_or_create_install_id(&dir).unwrap(), "abc");
+    }
+}
@@ -0,0 +1,280 @@
+use crate::cat;
+use crate::format::asciicast::{self, v1, Event, EventCode};
+use crate::format::{self, raw};
+use anyhow::bail;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    AsciicastV1,
+    AsciicastV2,
+    Raw,
+}
+
+/// Converts the recording `input` to `format`, or the one implied by the
+/// extension of `output`. An existing `output` is only replaced when
+/// `overwrite` is set.
+pub fn run(
+    input: &str,
+    output: &str,
+    format: Option<Format>,
+    assume_rate: Option<f64>,
+    overwrite: bool,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    let format = match format {
+        Some(format) => format,
+        None => format_for(output)?,
+    };
+
+    let data = fs::read(input)?;
+
+    if data.starts_with(b"{") {
+        let (header, events) = asciicast::open(&data[..])?;
+        let events = asciicast::limit(events, max_events);
+
+        write(output, format, &header, events, overwrite)
+    } else if format == Format::Raw {
+        // nothing to convert, apart from whatever follows the size
+        let (header, events) = read_raw(&data, 1.0);
+
+        write(output, format, &header, events, overwrite)
+    } else {
+        let Some(rate) = assume_rate else {
+            bail!(
+                "raw recording has no timing information, use --assume-rate to convert it anyway"
+            );
+        };
+
+        let (header, events) = read_raw(&data, rate);
+        let events = asciicast::limit(events, max_events);
+
+        write(output, format, &header, events, overwrite)
+    }
+}
+
+fn format_for<P: AsRef<Path>>(path: P) -> anyhow::Result<Format> {
+    match path.as_ref().extension().and_then(|e| e.to_str()) {
+        Some("cast") => Ok(Format::AsciicastV2),
+        Some("json") => Ok(Format::AsciicastV1),
+        Some("raw") | Some("txt") => Ok(Format::Raw),
+        _ => bail!("can't tell output format from the file extension, use --format"),
+    }
+}
+
+/// Turns raw output into events, as if it was printed line by line at
+/// `rate` bytes per second.
+pub fn read_raw(
+    data: &[u8],
+    rate: f64,
+) -> (
+    format::Header,
+    impl Iterator<Item = anyhow::Result<Event>> + '_,
+) {
+    let ((cols, rows), len) = raw::parse_size(data).unwrap_or(((80, 24), 0));
+
+    let header = format::Header {
+        cols,
+        rows,
+        timestamp: 0,
+        idle_time_limit: None,
+        command: None,
+        title: None,
+        env: HashMap::new(),
+        cwd: None,
+        theme: None,
+        original_term: None,
+    };
+
+    let mut offset = 0;
+
+    let events = data[len..]
+        .split_inclusive(|&b| b == b'\n')
+        .map(move |line| {
+            offset += line.len();
+
+            Ok(Event::output(offset as f64 / rate, line))
+        });
+
+    (header, events)
+}
+
+fn write<I>(
+    path: &str,
+    format: Format,
+    header: &format::Header,
+    events: I,
+    overwrite: bool,
+) -> anyhow::Result<()>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    let file = io::BufWriter::new(cat::create(path, overwrite)?);
+
+    match format {
+        // everything is kept as is, including markers and unknown events
+        Format::AsciicastV2 => {
+            let mut writer = asciicast::Writer::new(file, 0.0);
+            writer.write_header(&header.into())?;
+
+            for event in events {
+                writer.write_event(event?)?;
+            }
+        }
+
+        Format::AsciicastV1 => {
+            let mut writer = v1::Writer::new(file);
+            write_events(&mut writer, header, events)?;
+            writer.finish()?;
+        }
+
+        Format::Raw => {
+            let mut writer = raw::Writer::new(file);
+            write_events(&mut writer, header, events)?;
+            format::Writer::flush(&mut writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_events<W, I>(writer: &mut W, header: &format::Header, events: I) -> anyhow::Result<()>
+where
+    W: format::Writer,
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    writer.header(header)?;
+
+    for event in events {
+        let Event {
+            time,
+            code,
+            data,
+            label,
+        } = event?;
+
+        match code {
+            EventCode::Output => writer.output(time, data.as_bytes())?,
+            EventCode::Input => writer.input(time, data.as_bytes())?,
+
+            EventCode::Resize => {
+                let size = data
+                    .split_once('x')
+                    .and_then(|(cols, rows)| Some((cols.parse().ok()?, rows.parse().ok()?)));
+
+                if let Some(size) = size {
+                    writer.resize(time, size)?;
+                }
+            }
+
+            EventCode::Exit => {
+                if let Ok(status) = data.parse() {
+                    writer.exit(time, status)?;
+                }
+            }
+
+            EventCode::Marker => writer.marker(time, label.as_deref())?,
+            EventCode::Key => writer.key(time, &data)?,
+
+            EventCode::Other(_) => (),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+    use crate::format::asciicast;
+    use crate::test_util::TempDir;
+    use std::fs;
+    use std::io;
+
+    fn convert(input: &str, format: Format, assume_rate: Option<f64>) -> anyhow::Result<Vec<u8>> {
+        let dir = TempDir::new();
+        let output = dir.join(format!("{:?}", format));
+
+        super::run(
+            input,
+            output.to_str().unwrap(),
+            Some(format),
+            assume_rate,
+            false,
+            None,
+        )?;
+        Ok(fs::read(&output).unwrap())
+    }
+
+    #[test]
+    fn v2_to_raw() {
+        let data = convert("tests/demo.cast", Format::Raw, None).unwrap();
+        let file = fs::File::open("tests/demo.cast").unwrap();
+        let (_, events) = asciicast::open(io::BufReader::new(file)).unwrap();
+
+        let output = events
+            .map(Result::unwrap)
+            .filter(|e| e.code == asciicast::EventCode::Output)
+            .map(|e| e.data)
+            .collect::<String>();
+
+        assert_eq!(data, format!("\x1b[8;18;75t{}", output).as_bytes());
+    }
+
+    #[test]
+    fn v2_to_v1() {
+        let data = convert("tests/demo.cast", Format::AsciicastV1, None).unwrap();
+        let asciicast: serde_json::Value = serde_json::from_slice(&data).unwrap();
+
+        assert_eq!(asciicast["version"], 1);
+        assert_eq!(asciicast["width"], 75);
+        assert_eq!(asciicast["height"], 18);
+        assert_eq!(asciicast["stdout"].as_array().unwrap().len(), 30);
+        assert_eq!(asciicast["stdout"][0][0], 0.089436);
+        assert_eq!(asciicast["stdout"][1][0], 0.011553);
+        assert_eq!(asciicast["stdout"][1][1], "\u{1b}[?2004h");
+    }
+
+    #[test]
+    fn v3_to_v2() {
+        let data = convert("tests/demo-v3.cast", Format::AsciicastV2, None).unwrap();
+        let (header, events) = asciicast::open(&data[..]).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<_>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 24));
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].time, 3.75);
+        assert_eq!(events[2].data, "!");
+    }
+
+    #[test]
+    fn raw_to_v2() {
+        assert!(convert("tests/demo.raw", Format::AsciicastV2, None).is_err());
+
+        let data = convert("tests/demo.raw", Format::AsciicastV2, Some(11.0)).unwrap();
+        let (header, events) = asciicast::open(&data[..]).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<_>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 24));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].time, 1.0);
+        assert_eq!(events[0].data, "hello world");
+    }
+
+    #[test]
+    fn existing_output() {
+        let dir = TempDir::new();
+        let output = dir.join("demo.raw");
+        let output = output.to_str().unwrap();
+        fs::write(output, "existing").unwrap();
+
+        assert!(super::run("tests/demo.cast", output, None, None, false, None).is_err());
+        assert_eq!(fs::read_to_string(output).unwrap(), "existing");
+
+        super::run("tests/demo.cast", output, None, None, true, None).unwrap();
+        assert_ne!(fs::read_to_string(output).unwrap(), "existing");
+    }
+}
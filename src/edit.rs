@@ -0,0 +1,134 @@
+use crate::cat;
+use crate::format::asciicast::{self, Event};
+use crate::player;
+use anyhow::bail;
+use std::fs;
+use std::io;
+
+/// Writes the asciicast recording `input` to `output` with its timing
+/// rewritten: idle time limited to `idle_time_limit` seconds, the same way as
+/// on playback, and then sped up (or slowed down) by `speed`. Events are kept
+/// as they are otherwise, as is the header. An existing `output` is only
+/// replaced when `overwrite` is set, and never when it's `input` itself.
+pub fn run(
+    input: &str,
+    output: &str,
+    idle_time_limit: Option<f64>,
+    speed: f64,
+    overwrite: bool,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    if is_same_file(input, output) {
+        bail!("{} is the input recording, write to another file", output);
+    }
+
+    let reader = io::BufReader::new(fs::File::open(input)?);
+    let (header, events) = asciicast::open(reader)?;
+    let events = retime(asciicast::limit(events, max_events), idle_time_limit, speed);
+
+    let file = io::BufWriter::new(cat::create(output, overwrite)?);
+    let mut writer = asciicast::Writer::new(file, 0.0);
+    writer.write_header(&(&header).into())?;
+
+    for event in events {
+        writer.write_event(event?)?;
+    }
+
+    Ok(())
+}
+
+// the input is read while the output gets written, so creating (truncating)
+// the output must not touch the input
+fn is_same_file(input: &str, output: &str) -> bool {
+    match (fs::canonicalize(input), fs::canonicalize(output)) {
+        (Ok(input), Ok(output)) => input == output,
+        _ => false,
+    }
+}
+
+fn retime<I>(
+    events: I,
+    idle_time_limit: Option<f64>,
+    speed: f64,
+) -> impl Iterator<Item = anyhow::Result<Event>>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    player::limit_idle_time(events, idle_time_limit).map(move |event| {
+        event.map(|mut event| {
+            event.time /= speed;
+
+            event
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::asciicast::Event;
+
+    fn times(idle_time_limit: Option<f64>, speed: f64) -> Vec<f64> {
+        let events = [1.0, 6.0, 7.0, 17.0]
+            .into_iter()
+            .map(|time| Ok(Event::output(time, b".")));
+
+        super::retime(events, idle_time_limit, speed)
+            .map(|event| event.unwrap().time)
+            .collect()
+    }
+
+    #[test]
+    fn retime() {
+        assert_eq!(times(None, 1.0), vec![1.0, 6.0, 7.0, 17.0]);
+        assert_eq!(times(Some(2.0), 1.0), vec![1.0, 3.0, 4.0, 6.0]);
+        assert_eq!(times(None, 2.0), vec![0.5, 3.0, 3.5, 8.5]);
+        assert_eq!(times(Some(2.0), 2.0), vec![0.5, 1.5, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn run() {
+        let dir = crate::test_util::TempDir::new();
+        let output = dir.join("demo.cast");
+
+        super::run(
+            "tests/demo.cast",
+            output.to_str().unwrap(),
+            Some(0.5),
+            2.0,
+            false,
+            None,
+        )
+        .unwrap();
+
+        let original = crate::format::asciicast::get_duration("tests/demo.cast").unwrap();
+        let edited = crate::format::asciicast::get_duration(&output).unwrap();
+
+        assert!(edited < original / 2.0);
+    }
+
+    #[test]
+    fn run_existing_output() {
+        let dir = crate::test_util::TempDir::new();
+        let output = dir.join("demo.cast");
+        let output = output.to_str().unwrap();
+        std::fs::write(output, "existing").unwrap();
+
+        assert!(super::run("tests/demo.cast", output, None, 2.0, false, None).is_err());
+        assert_eq!(std::fs::read_to_string(output).unwrap(), "existing");
+
+        super::run("tests/demo.cast", output, None, 2.0, true, None).unwrap();
+        assert_ne!(std::fs::read_to_string(output).unwrap(), "existing");
+    }
+
+    #[test]
+    fn run_in_place() {
+        let dir = crate::test_util::TempDir::new();
+        let path = dir.join("demo.cast");
+        let path = path.to_str().unwrap();
+        std::fs::copy("tests/demo.cast", path).unwrap();
+        let original = std::fs::read_to_string(path).unwrap();
+
+        assert!(super::run(path, path, None, 2.0, true, None).is_err());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), original);
+    }
+}
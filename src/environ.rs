@@ -0,0 +1,166 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+pub fn env_patterns(env: &str, env_file: Option<&str>) -> Result<HashSet<String>> {
+    let mut patterns = env
+        .split(',')
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect::<HashSet<_>>();
+
+    if let Some(path) = env_file {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("can't read env file {}: {}", path, e))?;
+
+        patterns.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    Ok(patterns)
+}
+
+/// LANG tells players the locale the output was produced in.
+pub const DEFAULT_ENV: &str = "SHELL,TERM,LANG";
+
+const SENSITIVE_ENV_PATTERNS: [&str; 4] = ["*TOKEN*", "*SECRET*", "*PASSWORD*", "*KEY*"];
+
+/// Selects vars matching any of the patterns. With `redact`, vars which look
+/// sensitive are left out unless they were asked for by their exact name.
+/// `term` replaces the value of TERM, the same way `pty::build_exec_env` does.
+pub fn capture_env<I: Iterator<Item = (String, String)>>(
+    vars: I,
+    patterns: &HashSet<String>,
+    redact: bool,
+    term: Option<&str>,
+) -> HashMap<String, String> {
+    // lowercase names (e.g. github_token) are just as sensitive
+    let is_sensitive = |k: &str| {
+        let k = k.to_uppercase();
+        SENSITIVE_ENV_PATTERNS
+            .iter()
+            .any(|p| matches_pattern(p, &k))
+    };
+
+    vars.filter(|(k, _v)| {
+        patterns.contains(k)
+            || (patterns.iter().any(|p| matches_pattern(p, k)) && !(redact && is_sensitive(k)))
+    })
+    .map(|(k, v)| match term {
+        Some(term) if k == "TERM" => (k, term.to_owned()),
+        _ => (k, v),
+    })
+    .collect::<HashMap<_, _>>()
+}
+
+/// Matches `name` against `pattern`, where `*` stands for any sequence of
+/// characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+
+        Some((prefix, rest)) => name.strip_prefix(prefix).is_some_and(|name| {
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| matches_pattern(rest, &name[i..]))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempDir;
+    use std::fs;
+
+    #[test]
+    fn env_patterns() {
+        let dir = TempDir::new();
+        let path = dir.join("env");
+        fs::write(&path, "# captured vars\nLANG\n\n  LC_*  \nTERM\n").unwrap();
+
+        let mut patterns = super::env_patterns("SHELL,TERM", path.to_str())
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        patterns.sort();
+
+        assert_eq!(patterns, vec!["LANG", "LC_*", "SHELL", "TERM"]);
+        assert!(super::env_patterns("SHELL", Some("/nonexistent")).is_err());
+    }
+
+    #[test]
+    fn capture_env() {
+        let vars = || {
+            [
+                "SHELL",
+                "TERM",
+                "GITHUB_TOKEN",
+                "AWS_SECRET_ACCESS_KEY",
+                "DB_PASSWORD",
+                "SSH_AUTH_KEY",
+                "API_KEY",
+                "npm_config_token",
+            ]
+            .into_iter()
+            .map(|k| (k.to_owned(), "x".to_owned()))
+        };
+
+        let patterns = |p: &[&str]| p.iter().map(|p| p.to_string()).collect();
+        let names = |env: std::collections::HashMap<String, String>| {
+            let mut names = env.into_keys().collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let env = super::capture_env(vars(), &patterns(&["*"]), true, None);
+
+        assert_eq!(names(env), vec!["SHELL", "TERM"]);
+
+        let env = super::capture_env(vars(), &patterns(&["*", "API_KEY"]), true, None);
+
+        assert_eq!(names(env), vec!["API_KEY", "SHELL", "TERM"]);
+
+        let env = super::capture_env(vars(), &patterns(&["*"]), false, None);
+
+        assert_eq!(names(env).len(), 8);
+
+        let env = super::capture_env(vars(), &patterns(&["TERM"]), true, Some("xterm-256color"));
+
+        assert_eq!(env["TERM"], "xterm-256color");
+    }
+
+    #[test]
+    fn default_env() {
+        let vars = ["SHELL", "TERM", "LANG", "LC_ALL", "USER"]
+            .into_iter()
+            .map(|k| (k.to_owned(), "x".to_owned()));
+        let patterns = super::env_patterns(super::DEFAULT_ENV, None).unwrap();
+
+        let env = super::capture_env(vars, &patterns, true, None);
+        let mut names = env.into_keys().collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, vec!["LANG", "SHELL", "TERM"]);
+    }
+
+    #[test]
+    fn matches_pattern() {
+        use super::matches_pattern;
+
+        assert!(matches_pattern("TERM", "TERM"));
+        assert!(!matches_pattern("TERM", "TERMINAL"));
+        assert!(matches_pattern("LC_*", "LC_ALL"));
+        assert!(matches_pattern("LC_*", "LC_"));
+        assert!(!matches_pattern("LC_*", "LANG"));
+        assert!(matches_pattern("*_TOKEN", "GITHUB_TOKEN"));
+        assert!(matches_pattern("*TOK*N*", "MY_TOKEN_X"));
+        assert!(!matches_pattern("*TOKEN", "TOKENS"));
+        assert!(matches_pattern("*", "ANYTHING"));
+    }
+}
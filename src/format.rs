@@ -1,14 +1,28 @@
+pub mod ansi;
 pub mod asciicast;
 pub mod raw;
 use std::{collections::HashMap, io};
 
+/// Writes recordings event by event. `flush` gets retried when it's
+/// interrupted by a signal.
 pub trait Writer {
     fn header(&mut self, header: &Header) -> io::Result<()>;
     fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()>;
     fn input(&mut self, time: f64, data: &[u8]) -> io::Result<()>;
     fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()>;
+    fn exit(&mut self, time: f64, status: i32) -> io::Result<()>;
+    fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()>;
+    /// Name of a key found in the input, e.g. `C-c`, see `keys::Decoder`.
+    fn key(&mut self, time: f64, name: &str) -> io::Result<()>;
+    /// Makes sure everything written so far has reached the underlying sink,
+    /// e.g. before the process exits.
+    fn flush(&mut self) -> io::Result<()>;
 }
 
+/// Passes everything to both writers, e.g. to save a recording while also
+/// streaming it elsewhere. Both get written to even if the first one fails.
+pub struct Tee<A, B>(pub A, pub B);
+
 pub struct Header {
     pub cols: u16,
     pub rows: u16,
@@ -17,4 +31,137 @@ pub struct Header {
     pub command: Option<String>,
     pub title: Option<String>,
     pub env: HashMap<String, String>,
+    /// Working directory of the recorded command.
+    pub cwd: Option<String>,
+    /// Colors of the recorded terminal, for players to use.
+    pub theme: Option<asciicast::Theme>,
+    /// TERM of the user's terminal, when the recorded command got another.
+    pub original_term: Option<String>,
+}
+
+impl<W: Writer + ?Sized> Writer for Box<W> {
+    fn header(&mut self, header: &Header) -> io::Result<()> {
+        (**self).header(header)
+    }
+
+    fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        (**self).output(time, data)
+    }
+
+    fn input(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        (**self).input(time, data)
+    }
+
+    fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()> {
+        (**self).resize(time, size)
+    }
+
+    fn exit(&mut self, time: f64, status: i32) -> io::Result<()> {
+        (**self).exit(time, status)
+    }
+
+    fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()> {
+        (**self).marker(time, label)
+    }
+
+    fn key(&mut self, time: f64, name: &str) -> io::Result<()> {
+        (**self).key(time, name)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<A: Writer, B: Writer> Writer for Tee<A, B> {
+    fn header(&mut self, header: &Header) -> io::Result<()> {
+        let result = self.0.header(header);
+
+        result.and(self.1.header(header))
+    }
+
+    fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        let result = self.0.output(time, data);
+
+        result.and(self.1.output(time, data))
+    }
+
+    fn input(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        let result = self.0.input(time, data);
+
+        result.and(self.1.input(time, data))
+    }
+
+    fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()> {
+        let result = self.0.resize(time, size);
+
+        result.and(self.1.resize(time, size))
+    }
+
+    fn exit(&mut self, time: f64, status: i32) -> io::Result<()> {
+        let result = self.0.exit(time, status);
+
+        result.and(self.1.exit(time, status))
+    }
+
+    fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()> {
+        let result = self.0.marker(time, label);
+
+        result.and(self.1.marker(time, label))
+    }
+
+    fn key(&mut self, time: f64, name: &str) -> io::Result<()> {
+        let result = self.0.key(time, name);
+
+        result.and(self.1.key(time, name))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.0.flush();
+
+        result.and(self.1.flush())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{asciicast, raw, Header, Tee, Writer};
+    use std::collections::HashMap;
+
+    #[test]
+    fn tee() {
+        let mut file = Vec::new();
+        let mut stream = Vec::new();
+
+        let header = Header {
+            cols: 80,
+            rows: 24,
+            timestamp: 0,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: HashMap::new(),
+            cwd: None,
+            theme: None,
+            original_term: None,
+        };
+
+        let mut tee = Tee(
+            raw::Writer::new(&mut file),
+            asciicast::Writer::new(&mut stream, 0.0),
+        );
+
+        tee.header(&header).unwrap();
+        tee.output(0.5, b"foo").unwrap();
+        tee.input(1.0, b"x").unwrap();
+
+        assert_eq!(file, b"\x1b[8;24;80tfoo");
+
+        let stream = String::from_utf8(stream).unwrap();
+        let lines = stream.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "[0.5,\"o\",\"foo\"]");
+        assert_eq!(lines[2], "[1.0,\"i\",\"x\"]");
+    }
 }
@@ -0,0 +1,115 @@
+/// Removes escape sequences (CSI, OSC and the like) and control characters
+/// other than tab, newline and carriage return from terminal output, leaving
+/// plain text. Sequences may be split between chunks of data, so the state is
+/// kept from one call to the next.
+///
+/// Text of OSC 8 hyperlinks is kept, as it's displayed between the sequences
+/// opening and closing the link rather than inside them.
+#[derive(Debug, Default)]
+pub struct Stripper {
+    state: State,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    /// ESC followed by intermediate bytes, e.g. charset designation
+    EscapeIntermediate,
+    Csi,
+    /// OSC, DCS, SOS, PM or APC, terminated with ST (or BEL for OSC)
+    String,
+    StringEscape,
+}
+
+impl Stripper {
+    pub fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut text = Vec::with_capacity(data.len());
+
+        for &byte in data {
+            self.state = match (self.state, byte) {
+                (State::StringEscape, b'\\') => State::Ground,
+                (State::String, 0x07) => State::Ground,
+                (State::String, 0x1b) => State::StringEscape,
+                (State::String, _) => State::String,
+
+                (_, 0x1b) => State::Escape,
+
+                (State::Ground, b'\t' | b'\n' | b'\r' | 0x20..=0x7e | 0x80..) => {
+                    text.push(byte);
+                    State::Ground
+                }
+
+                (State::Ground, _) => State::Ground,
+
+                (State::Escape | State::StringEscape, b'[') => State::Csi,
+                (State::Escape | State::StringEscape, b']' | b'P' | b'X' | b'^' | b'_') => {
+                    State::String
+                }
+                (State::Escape | State::StringEscape, 0x20..=0x2f) => State::EscapeIntermediate,
+                (State::EscapeIntermediate, 0x20..=0x2f) => State::EscapeIntermediate,
+                (State::Csi, 0x40..=0x7e) => State::Ground,
+                (State::Csi, _) => State::Csi,
+
+                // final byte of a plain escape sequence
+                (_, _) => State::Ground,
+            };
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stripper;
+
+    fn strip(data: &[u8]) -> Vec<u8> {
+        Stripper::default().strip(data)
+    }
+
+    #[test]
+    fn strip_sgr_and_cursor_moves() {
+        assert_eq!(
+            strip(b"\x1b[1;31mred\x1b[0m \x1b[2J\x1b[Hplain\x1b[?25l\r\n"),
+            b"red plain\r\n"
+        );
+    }
+
+    #[test]
+    fn strip_osc() {
+        assert_eq!(strip(b"\x1b]0;title\x07a\x1b]8;;http://x\x1b\\b"), b"ab");
+    }
+
+    #[test]
+    fn strip_hyperlink() {
+        assert_eq!(
+            strip(b"see \x1b]8;;https://asciinema.org\x1b\\asciinema\x1b]8;;\x1b\\ now"),
+            b"see asciinema now"
+        );
+        assert_eq!(strip(b"\x1b]8;id=1;http://x\x07link\x1b]8;;\x07"), b"link");
+
+        let mut stripper = Stripper::default();
+
+        assert_eq!(stripper.strip(b"\x1b]8;;http://x\x1b"), b"");
+        assert_eq!(stripper.strip(b"\\li"), b"li");
+        assert_eq!(stripper.strip(b"nk\x1b]8;;\x1b\\."), b"nk.");
+    }
+
+    #[test]
+    fn strip_other() {
+        assert_eq!(strip(b"\x1b(Ba\x1b=b\x1bPq#0\x1b\\c\x08\x07"), b"abc");
+        assert_eq!(strip("zażółć\t!".as_bytes()), "zażółć\t!".as_bytes());
+    }
+
+    #[test]
+    fn split_sequence() {
+        let mut stripper = Stripper::default();
+
+        assert_eq!(stripper.strip(b"foo\x1b[3"), b"foo");
+        assert_eq!(stripper.strip(b"2mbar\x1b]0;ti"), b"bar");
+        assert_eq!(stripper.strip(b"tle\x1b"), b"");
+        assert_eq!(stripper.strip(b"\\baz"), b"baz");
+    }
+}
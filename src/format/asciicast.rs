@@ -1,14 +1,18 @@
+pub mod v1;
+
 use anyhow::bail;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{self, Display};
 use std::fs;
-use std::io::BufRead;
 use std::io::{self, Write};
+use std::io::{BufRead, Read};
 use std::path::Path;
+use std::str::FromStr;
+use std::vec;
 
 pub struct Writer<W: Write> {
-    writer: io::LineWriter<W>,
+    writer: W,
     time_offset: f64,
 }
 
@@ -16,17 +20,143 @@ pub struct Writer<W: Write> {
 pub struct Header {
     width: u16,
     height: u16,
+    #[serde(default)]
+    timestamp: u64,
+    idle_time_limit: Option<f32>,
+    command: Option<String>,
+    title: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    cwd: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    theme: Option<Theme>,
+    original_term: Option<String>,
+}
+
+/// Header of asciicast v3, which has the terminal's properties in `term`.
+#[derive(Deserialize)]
+struct V3Header {
+    term: V3Term,
+    #[serde(default)]
     timestamp: u64,
     idle_time_limit: Option<f32>,
     command: Option<String>,
     title: Option<String>,
+    #[serde(default)]
     env: HashMap<String, String>,
 }
 
+#[derive(Deserialize)]
+struct V3Term {
+    cols: u16,
+    rows: u16,
+    #[serde(default, deserialize_with = "lenient")]
+    theme: Option<Theme>,
+}
+
+/// Terminal colors, as `#rrggbb`, the palette being 8 or 16 of them separated
+/// with colons. Parsed from either a theme name or `FG:BG:PALETTE`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Theme {
+    pub fg: String,
+    pub bg: String,
+    pub palette: String,
+}
+
+/// Size cap of v1 recordings, which are read whole, the way `limit` caps
+/// events of later versions.
+const V1_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Themes of asciinema player, as (name, fg, bg, palette).
+const THEMES: &[(&str, &str, &str, &str)] = &[
+    (
+        "asciinema",
+        "#cccccc",
+        "#121314",
+        "#000000:#dd3c69:#4ebf22:#ddaf3c:#26b0d7:#b954e1:#54e1b9:#d9d9d9:\
+         #4d4d4d:#dd3c69:#4ebf22:#ddaf3c:#26b0d7:#b954e1:#54e1b9:#ffffff",
+    ),
+    (
+        "tango",
+        "#cccccc",
+        "#121314",
+        "#000000:#cc0000:#4e9a06:#c4a000:#3465a4:#75507b:#06989a:#d3d7cf:\
+         #555753:#ef2929:#8ae234:#fce94f:#729fcf:#ad7fa8:#34e2e2:#eeeeec",
+    ),
+    (
+        "solarized-dark",
+        "#839496",
+        "#002b36",
+        "#073642:#dc322f:#859900:#b58900:#268bd2:#d33682:#2aa198:#eee8d5:\
+         #002b36:#cb4b16:#586e75:#657b83:#839496:#6c71c4:#93a1a1:#fdf6e3",
+    ),
+    (
+        "solarized-light",
+        "#657b83",
+        "#fdf6e3",
+        "#073642:#dc322f:#859900:#b58900:#268bd2:#d33682:#2aa198:#eee8d5:\
+         #002b36:#cb4b16:#586e75:#657b83:#839496:#6c71c4:#93a1a1:#fdf6e3",
+    ),
+];
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, fg, bg, palette)) = THEMES.iter().find(|(name, ..)| *name == s) {
+            return Ok(Theme {
+                fg: fg.to_string(),
+                bg: bg.to_string(),
+                palette: palette.to_string(),
+            });
+        }
+
+        let colors = s.split(':').collect::<Vec<_>>();
+
+        if !matches!(colors.len(), 10 | 18) {
+            let names = THEMES.iter().map(|(name, ..)| *name).collect::<Vec<_>>();
+
+            return Err(format!(
+                "expected one of {} or FG:BG followed by 8 or 16 palette colors, separated with colons",
+                names.join(", ")
+            ));
+        }
+
+        if let Some(color) = colors.iter().find(|c| !is_color(c)) {
+            return Err(format!("invalid color {:?}, expected #rrggbb", color));
+        }
+
+        Ok(Theme {
+            fg: colors[0].to_owned(),
+            bg: colors[1].to_owned(),
+            palette: colors[2..].join(":"),
+        })
+    }
+}
+
+fn is_color(s: &str) -> bool {
+    s.strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Leaves out a malformed optional field, rather than failing to read the
+/// whole recording over it.
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+
+    Ok(value.and_then(|value| serde_json::from_value(value).ok()))
+}
+
 pub struct Event {
     pub time: f64,
     pub code: EventCode,
     pub data: String,
+    /// Optional fourth element of the event array, e.g. label of a marker.
+    pub label: Option<String>,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -35,7 +165,9 @@ pub enum EventCode {
     Input,
     Resize,
     Marker,
-    Other(char),
+    Exit,
+    Key,
+    Other(String),
 }
 
 impl<W> Writer<W>
@@ -44,19 +176,33 @@ where
 {
     pub fn new(writer: W, time_offset: f64) -> Self {
         Self {
-            writer: io::LineWriter::new(writer),
+            writer,
             time_offset,
         }
     }
 
+    /// The underlying writer, e.g. to take out what got written to a buffer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
     pub fn write_header(&mut self, header: &Header) -> io::Result<()> {
-        writeln!(self.writer, "{}", serde_json::to_string(&header)?)
+        self.write_line(serde_json::to_string(&header)?)
     }
 
     pub fn write_event(&mut self, mut event: Event) -> io::Result<()> {
         event.time += self.time_offset;
 
-        writeln!(self.writer, "{}", serde_json::to_string(&event)?)
+        self.write_line(serde_json::to_string(&event)?)
+    }
+
+    /// Every line gets written out as a whole right away, so if the recording
+    /// gets interrupted the file is valid up to the last event, with at most
+    /// one partially written line at the end.
+    fn write_line(&mut self, mut line: String) -> io::Result<()> {
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()
     }
 }
 
@@ -79,28 +225,213 @@ where
     fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()> {
         self.write_event(Event::resize(time, size))
     }
+
+    fn exit(&mut self, time: f64, status: i32) -> io::Result<()> {
+        self.write_event(Event::exit(time, status))
+    }
+
+    fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()> {
+        self.write_event(Event::marker(time, label))
+    }
+
+    fn key(&mut self, time: f64, name: &str) -> io::Result<()> {
+        self.write_event(Event::key(time, name))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
 }
 
-pub fn open<R: BufRead>(
+/// Lazily parses events, one line at a time, so memory use doesn't depend on
+/// the size of the recording.
+///
+/// Unterminated last line which doesn't parse is most likely a leftover of an
+/// interrupted recording, so it's skipped.
+pub struct EventIterator<R: BufRead> {
     reader: R,
-) -> anyhow::Result<(super::Header, impl Iterator<Item = anyhow::Result<Event>>)> {
-    let mut lines = reader.lines();
-    let first_line = lines.next().ok_or(anyhow::anyhow!("empty"))??;
-    let header: Header = serde_json::from_str(&first_line)?;
-    let header: super::Header = (&header).into();
+    line_no: usize,
+    /// Events of an asciicast v1 recording, which has to be read whole.
+    v1: Option<vec::IntoIter<Event>>,
+    /// Time of the previous event, for asciicast v3, where event times are
+    /// intervals since the previous event.
+    v3_time: Option<f64>,
+}
+
+impl<R: BufRead> Iterator for EventIterator<R> {
+    type Item = anyhow::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(events) = &mut self.v1 {
+            return events.next().map(Ok);
+        }
+
+        loop {
+            let mut line = Vec::new();
+            self.line_no += 1;
+
+            match self.reader.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Err(e) => return Some(Err(e.into())),
+                Ok(_) => (),
+            }
+
+            let terminated = line.ends_with(b"\n");
+
+            let line = match String::from_utf8(line) {
+                Ok(line) => line,
+                // cut off in the middle of a character
+                Err(_) if !terminated => return None,
+                Err(_) => {
+                    return Some(Err(anyhow::anyhow!("line {}: invalid UTF-8", self.line_no)))
+                }
+            };
+
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            // v3 allows comments
+            if line.is_empty() || (self.v3_time.is_some() && line.starts_with('#')) {
+                continue;
+            }
+
+            return match parse_event(line.to_owned(), self.line_no) {
+                Err(_) if !terminated => None,
+
+                Ok(mut event) => {
+                    if let Some(time) = &mut self.v3_time {
+                        *time += event.time.max(0.0);
+                        event.time = *time;
+                    }
+
+                    Some(Ok(event))
+                }
+
+                result => Some(result),
+            };
+        }
+    }
+}
+
+/// Size of the terminal at the end of the recording, i.e. the last resize or
+/// the one in the header.
+pub fn get_size<S: AsRef<Path>>(path: S) -> anyhow::Result<(u16, u16)> {
+    let file = fs::File::open(path)?;
+    let (header, events) = open(io::BufReader::new(file))?;
+    let mut size = (header.cols, header.rows);
+
+    for event in events {
+        let event = event?;
+
+        if event.code == EventCode::Resize {
+            if let Some(new_size) = parse_size(&event.data) {
+                size = new_size;
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+/// Parses the `COLSxROWS` data of a resize event.
+pub fn parse_size(data: &str) -> Option<(u16, u16)> {
+    let (cols, rows) = data.split_once('x')?;
+
+    Some((cols.parse().ok()?, rows.parse().ok()?))
+}
+
+/// Opens an asciicast v2 or v3 recording, or a v1 one, which gets read whole
+/// then. Events come with their absolute times, whatever the version.
+pub fn open<R: BufRead>(mut reader: R) -> anyhow::Result<(super::Header, EventIterator<R>)> {
+    let mut first_line = String::new();
+
+    if reader.read_line(&mut first_line)? == 0 {
+        bail!("empty");
+    }
+
+    let value = serde_json::from_str::<serde_json::Value>(&first_line);
+
+    // v1 is a single, usually pretty-printed, JSON document
+    let is_v1 = match &value {
+        Ok(value) => value["version"] == 1,
+        Err(e) => e.is_eof(),
+    };
 
-    let events = lines
-        .filter(|l| l.as_ref().map_or(true, |l| !l.is_empty()))
-        .enumerate()
-        .map(|(i, l)| l.map(|l| parse_event(l, i + 2))?);
+    if is_v1 {
+        let mut json = first_line;
+        read_v1(&mut reader, &mut json)?;
+        let (header, events) = v1::parse(&json)?;
+
+        let events = EventIterator {
+            reader,
+            line_no: 1,
+            v1: Some(events.into_iter()),
+            v3_time: None,
+        };
+
+        return Ok((header, events));
+    }
+
+    let (header, v3_time): (super::Header, _) = match value {
+        Ok(value) if value["version"] == 3 => {
+            let header: V3Header = serde_json::from_value(value)?;
+
+            ((&header).into(), Some(0.0))
+        }
+
+        _ => {
+            let header: Header = serde_json::from_str(&first_line)?;
+
+            ((&header).into(), None)
+        }
+    };
+
+    let events = EventIterator {
+        reader,
+        line_no: 1,
+        v1: None,
+        v3_time,
+    };
 
     Ok((header, events))
 }
 
+/// Reads the rest of a v1 recording into `json`, failing once it gets larger
+/// than `V1_MAX_SIZE`.
+fn read_v1<R: Read>(mut reader: R, json: &mut String) -> anyhow::Result<()> {
+    (&mut reader).take(V1_MAX_SIZE).read_to_string(json)?;
+
+    if reader.read(&mut [0])? > 0 {
+        bail!("asciicast v1 recording larger than {} bytes", V1_MAX_SIZE);
+    }
+
+    Ok(())
+}
+
+/// Limits number of events read from untrusted files, failing instead of
+/// processing more than `max_events`.
+pub fn limit<I>(events: I, max_events: Option<usize>) -> impl Iterator<Item = anyhow::Result<Event>>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    let max_events = max_events.unwrap_or(usize::MAX);
+
+    events.enumerate().scan(false, move |aborted, (i, event)| {
+        if *aborted {
+            None
+        } else if i >= max_events {
+            *aborted = true;
+            Some(Err(anyhow::anyhow!("more than {} events", max_events)))
+        } else {
+            Some(event)
+        }
+    })
+}
+
 fn parse_event(line: String, i: usize) -> anyhow::Result<Event> {
     use EventCode::*;
 
-    let value: serde_json::Value = serde_json::from_str(&line)?;
+    let value: serde_json::Value =
+        serde_json::from_str(&line).map_err(|e| anyhow::anyhow!("line {}: {}", i, e))?;
 
     let time = value[0]
         .as_f64()
@@ -111,7 +442,9 @@ fn parse_event(line: String, i: usize) -> anyhow::Result<Event> {
         Some("i") => Input,
         Some("r") => Resize,
         Some("m") => Marker,
-        Some(s) if !s.is_empty() => Other(s.chars().next().unwrap()),
+        Some("x") => Exit,
+        Some("k") => Key,
+        Some(s) if !s.is_empty() => Other(s.to_owned()),
         Some(_) => bail!("line {}: missing event code", i),
         None => bail!("line {}: event code must be a string", i),
     };
@@ -121,16 +454,219 @@ fn parse_event(line: String, i: usize) -> anyhow::Result<Event> {
         None => bail!("line {}: event data must be a string", i),
     };
 
-    Ok(Event { time, code, data })
+    let label = match value.get(3) {
+        Some(serde_json::Value::String(label)) => Some(label.to_owned()),
+        Some(_) => bail!("line {}: event label must be a string", i),
+        None => None,
+    };
+
+    Ok(Event {
+        time,
+        code,
+        data,
+        label,
+    })
 }
 
+/// Checks structural integrity of an asciicast v2, returning descriptions of
+/// all problems found. Other versions, though readable, aren't checked.
+pub fn lint<R: BufRead>(reader: R, max_events: Option<usize>) -> io::Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mut count = 0;
+    let mut lines = reader.split(b'\n').enumerate().map(|(i, l)| (i + 1, l));
+    let mut prev_time = 0.0;
+
+    let Some((_, line)) = lines.next() else {
+        problems.push("line 1: missing header".to_owned());
+        return Ok(problems);
+    };
+
+    match String::from_utf8(line?) {
+        Ok(line) => {
+            let line = line.trim_end_matches('\r');
+
+            // their events would all look broken as v2 ones
+            if let Some(version) = unchecked_version(line) {
+                problems.push(format!(
+                    "line 1: only asciicast v2 can be checked, this is v{}",
+                    version
+                ));
+
+                return Ok(problems);
+            }
+
+            if let Err(e) = lint_header(line) {
+                problems.push(format!("line 1: {}", e));
+            }
+        }
+
+        Err(_) => problems.push("line 1: invalid UTF-8".to_owned()),
+    }
+
+    for (i, line) in lines {
+        let mut line = line?;
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        count += 1;
+
+        if max_events.is_some_and(|max| count > max) {
+            problems.push(format!("line {}: more than {} events", i, count - 1));
+            break;
+        }
+
+        let Ok(line) = String::from_utf8(line) else {
+            problems.push(format!("line {}: invalid UTF-8", i));
+            continue;
+        };
+
+        match parse_event(line, i) {
+            Ok(event) => {
+                if event.time < prev_time {
+                    problems.push(format!(
+                        "line {}: event time {} is earlier than previous event time {}",
+                        i, event.time, prev_time
+                    ));
+                }
+
+                if let EventCode::Other(code) = event.code {
+                    problems.push(format!("line {}: unknown event code \"{}\"", i, code));
+                }
+
+                prev_time = event.time;
+            }
+
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Version of a recording `lint` can read but doesn't check, judging by its
+/// first line.
+fn unchecked_version(line: &str) -> Option<u64> {
+    match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(value) => value["version"].as_u64().filter(|v| *v == 1 || *v == 3),
+        // v1 is usually pretty-printed, so its header spans many lines
+        Err(e) if e.is_eof() => Some(1),
+        Err(_) => None,
+    }
+}
+
+fn lint_header(line: &str) -> anyhow::Result<()> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+
+    match value.get("version") {
+        Some(v) if v == 2 => (),
+        Some(v) => bail!("unsupported version {}", v),
+        None => bail!("missing version"),
+    }
+
+    serde_json::from_value::<Header>(value)?;
+
+    Ok(())
+}
+
+/// Returned by `get_duration` for files without timing information, i.e. raw
+/// recordings.
+#[derive(Debug)]
+pub struct UntimedError;
+
+impl Display for UntimedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not an asciicast file, no timing information available")
+    }
+}
+
+impl std::error::Error for UntimedError {}
+
+/// Returns the absolute time of the last event in asciicast v1, v2 or v3
+/// file.
 pub fn get_duration<S: AsRef<Path>>(path: S) -> anyhow::Result<f64> {
-    let file = fs::File::open(path)?;
-    let reader = io::BufReader::new(file);
-    let (_header, events) = open(reader)?;
-    let time = events.last().map_or(Ok(0.0), |e| e.map(|e| e.time))?;
+    let file = fs::File::open(&path)?;
+    let mut reader = io::BufReader::new(file);
+    let mut first_line = String::new();
+
+    // invalid UTF-8 is as good a sign of a raw recording as invalid JSON
+    let header = reader
+        .read_line(&mut first_line)
+        .ok()
+        .and_then(|_| serde_json::from_str::<serde_json::Value>(&first_line).ok());
+
+    match header.as_ref().and_then(|h| h.get("version")) {
+        Some(v) if v == 2 || v == 3 => {
+            let file = fs::File::open(&path)?;
+            let (_header, mut events) = open(io::BufReader::new(file))?;
+
+            // the latest event, in case times go backwards somewhere
+            events.try_fold(0.0, |duration: f64, e| Ok(duration.max(e?.time)))
+        }
+
+        Some(v) if v != 1 => bail!("unsupported asciicast version {}", v),
+
+        // v1 is a single, usually pretty-printed, JSON document
+        _ => {
+            let start = first_line.trim_start();
+
+            // raw output needn't be read whole to tell it's not JSON, and
+            // invalid UTF-8 leaves the line empty
+            if first_line.is_empty() || !(start.is_empty() || start.starts_with('{')) {
+                return Err(UntimedError.into());
+            }
+
+            let mut json = first_line;
+
+            match read_v1(&mut reader, &mut json) {
+                Err(e) if e.is::<io::Error>() => return Err(UntimedError.into()),
+                result => result?,
+            }
+
+            let value: serde_json::Value = match serde_json::from_str(&json) {
+                Ok(value) => value,
+
+                // a mangled header rather than raw output, which mustn't be
+                // taken for a recording without timing
+                Err(_) if json.trim_start().starts_with('{') => {
+                    bail!(
+                        "{} has an invalid asciicast header",
+                        path.as_ref().display()
+                    )
+                }
+
+                Err(_) => return Err(UntimedError.into()),
+            };
+
+            if !value.is_object() {
+                return Err(UntimedError.into());
+            }
+
+            if value["version"] != 1 {
+                bail!(
+                    "{} has an invalid asciicast header",
+                    path.as_ref().display()
+                );
+            }
 
-    Ok(time)
+            let stdout = value["stdout"]
+                .as_array()
+                .ok_or(anyhow::anyhow!("missing stdout"))?;
+
+            stdout.iter().try_fold(0.0, |duration, frame| {
+                let delay = frame[0]
+                    .as_f64()
+                    .ok_or(anyhow::anyhow!("invalid frame delay"))?;
+
+                Ok(duration + delay.max(0.0))
+            })
+        }
+    }
 }
 
 impl Event {
@@ -139,6 +675,7 @@ impl Event {
             time,
             code: EventCode::Output,
             data: String::from_utf8_lossy(data).to_string(),
+            label: None,
         }
     }
 
@@ -147,6 +684,7 @@ impl Event {
             time,
             code: EventCode::Input,
             data: String::from_utf8_lossy(data).to_string(),
+            label: None,
         }
     }
 
@@ -155,6 +693,40 @@ impl Event {
             time,
             code: EventCode::Resize,
             data: format!("{}x{}", size.0, size.1),
+            label: None,
+        }
+    }
+
+    /// `status` is the exit status of the recorded command, or negated
+    /// signal number if it was killed by a signal.
+    pub fn exit(time: f64, status: i32) -> Self {
+        Event {
+            time,
+            code: EventCode::Exit,
+            data: status.to_string(),
+            label: None,
+        }
+    }
+
+    /// Labelled markers are written in the 4-element form, `[time, "m", "",
+    /// label]`.
+    pub fn marker(time: f64, label: Option<&str>) -> Self {
+        Event {
+            time,
+            code: EventCode::Marker,
+            data: String::new(),
+            label: label.map(String::from),
+        }
+    }
+
+    /// Name of a pressed key, recorded next to the raw input with
+    /// `rec --input-names`, e.g. `[time, "k", "C-c"]`.
+    pub fn key(time: f64, name: &str) -> Self {
+        Event {
+            time,
+            code: EventCode::Key,
+            data: name.to_owned(),
+            label: None,
         }
     }
 }
@@ -168,7 +740,9 @@ impl Display for EventCode {
             Input => f.write_str("i"),
             Resize => f.write_str("r"),
             Marker => f.write_str("m"),
-            Other(t) => f.write_str(&t.to_string()),
+            Exit => f.write_str("x"),
+            Key => f.write_str("k"),
+            Other(t) => f.write_str(t),
         }
     }
 }
@@ -198,6 +772,18 @@ impl serde::Serialize for Header {
             len += 1;
         }
 
+        if self.cwd.is_some() {
+            len += 1;
+        }
+
+        if self.theme.is_some() {
+            len += 1;
+        }
+
+        if self.original_term.is_some() {
+            len += 1;
+        }
+
         let mut map = serializer.serialize_map(Some(len))?;
         map.serialize_entry("version", &2)?;
         map.serialize_entry("width", &self.width)?;
@@ -220,6 +806,18 @@ impl serde::Serialize for Header {
             map.serialize_entry("env", &self.env)?;
         }
 
+        if let Some(cwd) = &self.cwd {
+            map.serialize_entry("cwd", &cwd)?;
+        }
+
+        if let Some(theme) = &self.theme {
+            map.serialize_entry("theme", theme)?;
+        }
+
+        if let Some(term) = &self.original_term {
+            map.serialize_entry("original_term", term)?;
+        }
+
         map.end()
     }
 }
@@ -230,14 +828,37 @@ impl serde::Serialize for Event {
         S: serde::Serializer,
     {
         use serde::ser::SerializeTuple;
-        let mut tup = serializer.serialize_tuple(3)?;
+        let len = if self.label.is_some() { 4 } else { 3 };
+        let mut tup = serializer.serialize_tuple(len)?;
         tup.serialize_element(&self.time)?;
         tup.serialize_element(&self.code.to_string())?;
         tup.serialize_element(&self.data)?;
+
+        if let Some(label) = &self.label {
+            tup.serialize_element(label)?;
+        }
+
         tup.end()
     }
 }
 
+impl From<&V3Header> for super::Header {
+    fn from(header: &V3Header) -> Self {
+        Self {
+            cols: header.term.cols,
+            rows: header.term.rows,
+            timestamp: header.timestamp,
+            idle_time_limit: header.idle_time_limit,
+            command: header.command.clone(),
+            title: header.title.clone(),
+            env: header.env.clone(),
+            cwd: None,
+            theme: header.term.theme.clone(),
+            original_term: None,
+        }
+    }
+}
+
 impl From<&Header> for super::Header {
     fn from(header: &Header) -> Self {
         Self {
@@ -248,6 +869,9 @@ impl From<&Header> for super::Header {
             command: header.command.clone(),
             title: header.title.clone(),
             env: header.env.clone(),
+            cwd: header.cwd.clone(),
+            theme: header.theme.clone(),
+            original_term: header.original_term.clone(),
         }
     }
 }
@@ -262,6 +886,9 @@ impl From<&super::Header> for Header {
             command: header.command.clone(),
             title: header.title.clone(),
             env: header.env.clone(),
+            cwd: header.cwd.clone(),
+            theme: header.theme.clone(),
+            original_term: header.original_term.clone(),
         }
     }
 }
@@ -271,7 +898,35 @@ mod tests {
     use super::{Event, EventCode, Header, Writer};
     use std::collections::HashMap;
     use std::fs::File;
-    use std::io;
+    use std::io::{self, Read};
+
+    #[test]
+    fn open_v1() {
+        let file = File::open("tests/demo.json").unwrap();
+        let (header, events) = super::open(io::BufReader::new(file)).unwrap();
+
+        let events = events.collect::<anyhow::Result<Vec<Event>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 40));
+        assert_eq!(header.command.as_deref(), Some("/bin/bash"));
+        assert_eq!(header.env["SHELL"], "/bin/bash");
+
+        assert_eq!(events[0].time, 0.013659);
+        assert_eq!(events[0].code, EventCode::Output);
+        assert_eq!(events[0].data, "\u{1b}[?1034hbash-3.2$ ");
+        assert!((events[1].time - 1.936846).abs() < 1e-9);
+        assert!((events.last().unwrap().time - 6.456541).abs() < 1e-9);
+    }
+
+    #[test]
+    fn open_v1_too_large() {
+        let json = io::Cursor::new("{\"version\": 1,\n")
+            .chain(io::repeat(b' ').take(super::V1_MAX_SIZE + 1));
+
+        let result = super::open(io::BufReader::new(json));
+
+        assert!(result.is_err());
+    }
 
     #[test]
     fn open() {
@@ -298,6 +953,66 @@ mod tests {
         assert_eq!(events[6].data, "v");
     }
 
+    #[test]
+    fn open_v3() {
+        let file = File::open("tests/demo-v3.cast").unwrap();
+        let (header, events) = super::open(io::BufReader::new(file)).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<Event>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 24));
+        assert_eq!(header.timestamp, 1700000000);
+
+        let times = events.iter().map(|e| e.time).collect::<Vec<_>>();
+
+        assert_eq!(times, vec![0.5, 1.75, 3.75]);
+        assert_eq!(events[1].data, " world");
+    }
+
+    #[test]
+    fn truncated() {
+        let data = std::fs::read("tests/demo.cast").unwrap();
+        let data = &data[..data.len() - 10];
+        let (_, events) = super::open(data).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(events.len(), 38);
+
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"foo\"]";
+        let (_, events) = super::open(&data[..]).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(events.len(), 1);
+
+        // cut off in the middle of a multibyte character
+        let data = "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+                    [1.0, \"o\", \"foo\"]\n\
+                    [2.0, \"o\", \"é";
+        let data = &data.as_bytes()[..data.len() - 1];
+        let (_, events) = super::open(data).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<Event>>>().unwrap();
+
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn event_iterator() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"o\", \"foo\"]\n\
+\n\
+[2.0, \"o\", \"bar\"]\n\
+[3.0, \"o\"]\n";
+
+        let (_, mut events) = super::open(&data[..]).unwrap();
+
+        assert_eq!(events.next().unwrap().unwrap().data, "foo");
+        assert_eq!(events.next().unwrap().unwrap().data, "bar");
+
+        let error = events.next().unwrap().err().unwrap();
+
+        assert_eq!(error.to_string(), "line 5: event data must be a string");
+        assert!(events.next().is_none());
+    }
+
     #[test]
     fn writer() {
         let mut data = Vec::new();
@@ -314,6 +1029,9 @@ mod tests {
                 command: None,
                 title: None,
                 env: Default::default(),
+                cwd: None,
+                theme: None,
+                original_term: None,
             };
 
             fw.write_header(&header).unwrap();
@@ -331,6 +1049,7 @@ mod tests {
                 .unwrap();
             fw.write_event(Event::input(2.0, " ".as_bytes())).unwrap();
             fw.write_event(Event::resize(3.0, (100, 40))).unwrap();
+            fw.write_event(Event::exit(4.0, -15)).unwrap();
         }
 
         let lines = parse(data);
@@ -351,6 +1070,74 @@ mod tests {
         assert_eq!(lines[4][0], 4.0);
         assert_eq!(lines[4][1], "r");
         assert_eq!(lines[4][2], "100x40");
+        assert_eq!(lines[5][0], 5.0);
+        assert_eq!(lines[5][1], "x");
+        assert_eq!(lines[5][2], "-15");
+    }
+
+    #[test]
+    fn marker() {
+        let mut data = Vec::new();
+        let mut fw = Writer::new(&mut data, 0.0);
+
+        fw.write_event(Event::marker(1.0, None)).unwrap();
+        fw.write_event(Event::marker(2.0, Some("intro"))).unwrap();
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "[1.0,\"m\",\"\"]\n[2.0,\"m\",\"\",\"intro\"]\n"
+        );
+
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"m\", \"\"]\n\
+[2.0, \"m\", \"\", \"intro\"]\n\
+[3.0, \"m\", \"\", 3]\n";
+
+        let (_, mut events) = super::open(&data[..]).unwrap();
+
+        assert_eq!(events.next().unwrap().unwrap().label, None);
+        assert_eq!(
+            events.next().unwrap().unwrap().label.as_deref(),
+            Some("intro")
+        );
+
+        let error = events.next().unwrap().err().unwrap();
+
+        assert_eq!(error.to_string(), "line 4: event label must be a string");
+    }
+
+    #[test]
+    fn idle_time_limit() {
+        use crate::format::{self, Writer as _};
+
+        let header = |idle_time_limit| {
+            let mut data = Vec::new();
+            let mut fw = Writer::new(&mut data, 0.0);
+
+            fw.header(&format::Header {
+                cols: 80,
+                rows: 24,
+                timestamp: 1,
+                idle_time_limit,
+                command: None,
+                title: None,
+                env: HashMap::new(),
+                cwd: None,
+                theme: None,
+                original_term: None,
+            })
+            .unwrap();
+
+            parse(data).remove(0)
+        };
+
+        assert_eq!(header(Some(2.5))["idle_time_limit"], 2.5);
+        assert!(header(None).get("idle_time_limit").is_none());
+
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24, \"idle_time_limit\": 2.5}\n";
+        let (header, _) = super::open(&data[..]).unwrap();
+
+        assert_eq!(header.idle_time_limit, Some(2.5));
     }
 
     #[test]
@@ -372,6 +1159,9 @@ mod tests {
                 command: Some("/bin/bash".to_owned()),
                 title: Some("Demo".to_owned()),
                 env,
+                cwd: Some("/home/demo".to_owned()),
+                theme: Some("solarized-dark".parse().unwrap()),
+                original_term: Some("screen-256color".to_owned()),
             };
 
             fw.write_header(&header).unwrap();
@@ -389,6 +1179,213 @@ mod tests {
         assert_eq!(lines[0]["env"].as_object().unwrap().len(), 2);
         assert_eq!(lines[0]["env"]["SHELL"], "/usr/bin/fish");
         assert_eq!(lines[0]["env"]["TERM"], "xterm256-color");
+        assert_eq!(lines[0]["cwd"], "/home/demo");
+        assert_eq!(lines[0]["original_term"], "screen-256color");
+        assert_eq!(lines[0]["theme"]["fg"], "#839496");
+        assert_eq!(lines[0]["theme"]["bg"], "#002b36");
+        assert_eq!(
+            lines[0]["theme"]["palette"].as_str().unwrap().len(),
+            16 * 8 - 1
+        );
+    }
+
+    #[test]
+    fn write_zero_idle_time_limit() {
+        let mut data = Vec::new();
+
+        let header = Header {
+            width: 80,
+            height: 24,
+            timestamp: 1,
+            idle_time_limit: Some(0.0),
+            command: None,
+            title: None,
+            env: HashMap::new(),
+            cwd: None,
+            theme: None,
+            original_term: None,
+        };
+
+        Writer::new(&mut data, 0.0).write_header(&header).unwrap();
+        let (header, _) = super::open(&data[..]).unwrap();
+
+        assert_eq!(header.idle_time_limit, Some(0.0));
+    }
+
+    #[test]
+    fn theme() {
+        use super::Theme;
+
+        let theme: Theme = "tango".parse().unwrap();
+
+        assert_eq!(theme.fg, "#cccccc");
+        assert_eq!(theme.palette.split(':').count(), 16);
+
+        let theme: Theme =
+            "#ffffff:#000000:#000000:#aa0000:#00aa00:#aa5500:#0000aa:#aa00aa:#00aaaa:#aaaaaa"
+                .parse()
+                .unwrap();
+
+        assert_eq!(theme.fg, "#ffffff");
+        assert_eq!(theme.bg, "#000000");
+        assert_eq!(theme.palette.split(':').count(), 8);
+
+        assert!("nope".parse::<Theme>().is_err());
+        assert!("#ffffff:#000000:#000000".parse::<Theme>().is_err());
+
+        let error = "#ffffff:#000000:#000000:#aa0000:#00aa00:#aa5500:#0000aa:#aa00aa:#00aaaa:red"
+            .parse::<Theme>()
+            .unwrap_err();
+
+        assert!(error.contains("\"red\""));
+        assert!(
+            "#fff:#000000:#000000:#aa0000:#00aa00:#aa5500:#0000aa:#aa00aa:#00aaaa:#aaaaaa"
+                .parse::<Theme>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn read_theme() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24, \"theme\": {\"fg\": \"#ffffff\", \"bg\": \"#000000\", \"palette\": \"#000000:#ffffff\"}}\n";
+        let (header, _) = super::open(&data[..]).unwrap();
+
+        assert_eq!(header.theme.unwrap().fg, "#ffffff");
+
+        // a malformed theme doesn't make the recording unreadable
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24, \"theme\": \"dark\"}\n";
+        let (header, _) = super::open(&data[..]).unwrap();
+
+        assert!(header.theme.is_none());
+    }
+
+    #[test]
+    fn lint() {
+        let file = File::open("tests/demo.cast").unwrap();
+
+        assert!(super::lint(io::BufReader::new(file), None)
+            .unwrap()
+            .is_empty());
+
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24, \"timestamp\": 1}\n\
+[1.0, \"o\", \"foo\"]\n\
+[0.5, \"o\", \"bar\"]\n\
+\n\
+[2.0, \"z\", \"baz\"]\n\
+[3.0, \"o\", \"\xff\"]\n\
+[4.0, \"o\", \n\
+[5.0, \"i\", 1]\n";
+
+        let problems = super::lint(&data[..], None).unwrap();
+
+        assert_eq!(problems.len(), 5);
+        assert!(problems[0].starts_with("line 3: event time 0.5 is earlier"));
+        assert_eq!(problems[1], "line 5: unknown event code \"z\"");
+        assert_eq!(problems[2], "line 6: invalid UTF-8");
+        assert!(problems[3].starts_with("line 7: "));
+        assert_eq!(problems[4], "line 8: event data must be a string");
+
+        let problems = super::lint(&b"{\"version\": 4, \"width\": 80}\n"[..], None).unwrap();
+
+        assert_eq!(problems, vec!["line 1: unsupported version 4"]);
+
+        for (path, version) in [("tests/demo.json", 1), ("tests/demo-v3.cast", 3)] {
+            let file = File::open(path).unwrap();
+            let problems = super::lint(io::BufReader::new(file), None).unwrap();
+
+            assert_eq!(
+                problems,
+                vec![format!(
+                    "line 1: only asciicast v2 can be checked, this is v{}",
+                    version
+                )]
+            );
+        }
+
+        let problems = super::lint(&b"{\"version\": 2, \"width\": 80}\n"[..], None).unwrap();
+
+        assert!(problems[0].starts_with("line 1: missing field `height`"));
+        assert_eq!(
+            super::lint(&b""[..], None).unwrap(),
+            vec!["line 1: missing header"]
+        );
+    }
+
+    #[test]
+    fn limit() {
+        let file = File::open("tests/demo.cast").unwrap();
+        let (_header, events) = super::open(io::BufReader::new(file)).unwrap();
+        let events = super::limit(events, Some(5)).collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 6);
+        assert!(events[..5].iter().all(|e| e.is_ok()));
+        assert_eq!(
+            events[5].as_ref().err().unwrap().to_string(),
+            "more than 5 events"
+        );
+
+        let file = File::open("tests/demo.cast").unwrap();
+        let problems = super::lint(io::BufReader::new(file), Some(5)).unwrap();
+
+        assert_eq!(problems, vec!["line 7: more than 5 events"]);
+    }
+
+    #[test]
+    fn get_duration() {
+        assert_eq!(super::get_duration("tests/demo.cast").unwrap(), 11.89348);
+        assert!((super::get_duration("tests/demo.json").unwrap() - 6.456541).abs() < 1e-9);
+        assert_eq!(super::get_duration("tests/demo-v3.cast").unwrap(), 3.75);
+        assert_eq!(
+            super::get_duration("tests/out-of-order.cast").unwrap(),
+            10.5
+        );
+
+        let error = super::get_duration("tests/demo.raw").unwrap_err();
+
+        assert!(error.is::<super::UntimedError>());
+
+        // a corrupt header doesn't make it a raw recording
+        let dir = crate::test_util::TempDir::new();
+        let path = dir.join("corrupt.cast");
+
+        for data in [
+            "{\"version\": 2, \"width\": 80\n[1.0, \"o\", \"foo\"]\n",
+            "{\"width\": 80, \"height\": 24}\n[1.0, \"o\", \"foo\"]\n",
+        ] {
+            std::fs::write(&path, data).unwrap();
+            let error = super::get_duration(&path).unwrap_err();
+
+            assert!(!error.is::<super::UntimedError>());
+        }
+
+        // v1 gets read whole, but no more than V1_MAX_SIZE of it
+        let mut data = b"{\"version\": 1,\n".to_vec();
+        data.resize(data.len() + super::V1_MAX_SIZE as usize + 1, b' ');
+        std::fs::write(&path, data).unwrap();
+        let error = super::get_duration(&path).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .starts_with("asciicast v1 recording larger than"));
+    }
+
+    #[test]
+    fn get_size() {
+        assert_eq!(super::get_size("tests/demo.cast").unwrap(), (75, 18));
+
+        let dir = crate::test_util::TempDir::new();
+        let path = dir.join("demo.cast");
+
+        std::fs::write(
+            &path,
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"r\", \"100x30\"]\n\
+[2.0, \"o\", \"$ \"]\n\
+[3.0, \"r\", \"bogus\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(super::get_size(&path).unwrap(), (100, 30));
     }
 
     fn parse(json: Vec<u8>) -> Vec<serde_json::Value> {
@@ -0,0 +1,295 @@
+use super::Event;
+use crate::format;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// asciicast v1 is a single JSON document, so nothing gets written until
+/// `finish` is called.
+pub struct Writer<W: Write> {
+    writer: W,
+    header: Option<Header>,
+    stdout: Vec<(f64, String)>,
+    duration: f64,
+}
+
+#[derive(Serialize)]
+struct Header {
+    width: u16,
+    height: u16,
+    command: Option<String>,
+    title: Option<String>,
+    env: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Document {
+    version: u8,
+    width: u16,
+    height: u16,
+    command: Option<String>,
+    title: Option<String>,
+    env: Option<HashMap<String, String>>,
+    stdout: Vec<(f64, String)>,
+}
+
+#[derive(Serialize)]
+struct Asciicast<'a> {
+    version: u8,
+    #[serde(flatten)]
+    header: &'a Header,
+    duration: f64,
+    stdout: Vec<(f64, &'a str)>,
+}
+
+impl<W> Writer<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header: None,
+            stdout: Vec::new(),
+            duration: 0.0,
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        let header = self
+            .header
+            .as_ref()
+            .ok_or(io::Error::other("missing header"))?;
+
+        let mut prev_time = 0.0;
+
+        let stdout = self
+            .stdout
+            .iter()
+            .map(|(time, data)| {
+                let delay = round(time - prev_time);
+                prev_time = *time;
+
+                (delay, data.as_str())
+            })
+            .collect();
+
+        let asciicast = Asciicast {
+            version: 1,
+            header,
+            duration: round(self.duration),
+            stdout,
+        };
+
+        serde_json::to_writer(&mut self.writer, &asciicast)?;
+        writeln!(self.writer)?;
+        self.writer.flush()
+    }
+
+    fn update_duration(&mut self, time: f64) {
+        self.duration = self.duration.max(time);
+    }
+}
+
+/// Reads a whole asciicast v1 document, turning delays of its frames into
+/// times of output events.
+pub fn parse(json: &str) -> anyhow::Result<(format::Header, Vec<Event>)> {
+    let document: Document = serde_json::from_str(json)?;
+
+    if document.version != 1 {
+        bail!("unsupported asciicast version {}", document.version);
+    }
+
+    let header = format::Header {
+        cols: document.width,
+        rows: document.height,
+        timestamp: 0,
+        idle_time_limit: None,
+        command: document.command,
+        title: document.title,
+        env: document.env.unwrap_or_default(),
+        cwd: None,
+        theme: None,
+        original_term: None,
+    };
+
+    let mut time = 0.0;
+
+    let events = document
+        .stdout
+        .into_iter()
+        .map(|(delay, data)| {
+            time += delay.max(0.0);
+
+            Event::output(time, data.as_bytes())
+        })
+        .collect();
+
+    Ok((header, events))
+}
+
+/// Frame delays have microsecond precision.
+fn round(secs: f64) -> f64 {
+    (secs * 1_000_000.0).round() / 1_000_000.0
+}
+
+impl<W> crate::format::Writer for Writer<W>
+where
+    W: Write,
+{
+    fn header(&mut self, header: &crate::format::Header) -> io::Result<()> {
+        self.header = Some(Header {
+            width: header.cols,
+            height: header.rows,
+            command: header.command.clone(),
+            title: header.title.clone(),
+            env: header.env.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        self.update_duration(time);
+        self.stdout
+            .push((time, String::from_utf8_lossy(data).to_string()));
+
+        Ok(())
+    }
+
+    // v1 has no concept of the events below
+
+    fn input(&mut self, time: f64, _data: &[u8]) -> io::Result<()> {
+        self.update_duration(time);
+
+        Ok(())
+    }
+
+    fn resize(&mut self, time: f64, _size: (u16, u16)) -> io::Result<()> {
+        self.update_duration(time);
+
+        Ok(())
+    }
+
+    fn exit(&mut self, time: f64, _status: i32) -> io::Result<()> {
+        self.update_duration(time);
+
+        Ok(())
+    }
+
+    fn marker(&mut self, time: f64, _label: Option<&str>) -> io::Result<()> {
+        self.update_duration(time);
+
+        Ok(())
+    }
+
+    fn key(&mut self, time: f64, _name: &str) -> io::Result<()> {
+        self.update_duration(time);
+
+        Ok(())
+    }
+
+    // nothing gets written before `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Writer;
+    use crate::format::asciicast::{self, EventCode};
+    use crate::format::{self, Writer as _};
+    use std::collections::HashMap;
+
+    #[test]
+    fn writer() {
+        let mut data = Vec::new();
+        let mut fw = Writer::new(&mut data);
+
+        let header = format::Header {
+            cols: 80,
+            rows: 24,
+            timestamp: 1,
+            idle_time_limit: None,
+            command: Some("/bin/bash".to_owned()),
+            title: None,
+            env: HashMap::new(),
+            cwd: None,
+            theme: None,
+            original_term: None,
+        };
+
+        fw.header(&header).unwrap();
+        fw.output(1.0, b"foo").unwrap();
+        fw.input(1.2, b"x").unwrap();
+        fw.output(1.5, b"bar").unwrap();
+        fw.resize(2.25, (100, 40)).unwrap();
+        fw.finish().unwrap();
+
+        let asciicast: serde_json::Value = serde_json::from_slice(&data).unwrap();
+
+        assert_eq!(asciicast["version"], 1);
+        assert_eq!(asciicast["width"], 80);
+        assert_eq!(asciicast["height"], 24);
+        assert_eq!(asciicast["duration"], 2.25);
+        assert_eq!(asciicast["command"], "/bin/bash");
+        assert_eq!(asciicast["title"], serde_json::Value::Null);
+        assert_eq!(
+            asciicast["stdout"],
+            serde_json::json!([[1.0, "foo"], [0.5, "bar"]])
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let json = r#"{"version": 1, "width": 80, "height": 24, "duration": 1.5,
+            "command": null, "title": "Demo", "env": null,
+            "stdout": [[0.5, "foo"], [1.0, "bar"]]}"#;
+
+        let (header, events) = super::parse(json).unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 24));
+        assert_eq!(header.title.as_deref(), Some("Demo"));
+        assert!(header.env.is_empty());
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].time, events[0].data.as_str()), (0.5, "foo"));
+        assert_eq!((events[1].time, events[1].data.as_str()), (1.5, "bar"));
+
+        let json = r#"{"version": 2, "width": 80, "height": 24, "stdout": []}"#;
+
+        assert!(super::parse(json).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = std::fs::File::open("tests/demo.cast").unwrap();
+        let (header, events) = asciicast::open(std::io::BufReader::new(file)).unwrap();
+        let output = events
+            .map(Result::unwrap)
+            .filter(|e| e.code == EventCode::Output)
+            .collect::<Vec<_>>();
+
+        let mut data = Vec::new();
+        let mut fw = Writer::new(&mut data);
+        fw.header(&header).unwrap();
+
+        for event in &output {
+            fw.output(event.time, event.data.as_bytes()).unwrap();
+        }
+
+        fw.finish().unwrap();
+
+        let (v1_header, v1_events) = asciicast::open(&data[..]).unwrap();
+        let v1_events = v1_events.map(Result::unwrap).collect::<Vec<_>>();
+
+        assert_eq!((v1_header.cols, v1_header.rows), (header.cols, header.rows));
+        assert_eq!(v1_events.len(), output.len());
+
+        for (a, b) in v1_events.iter().zip(&output) {
+            assert!((a.time - b.time).abs() < 1e-5);
+            assert_eq!(a.data, b.data);
+        }
+    }
+}
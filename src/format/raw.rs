@@ -1,29 +1,125 @@
 use std::io::{self, Write};
 
+/// Clears the screen and moves the cursor home.
+pub const RESET: &[u8] = b"\x1b[2J\x1b[H";
+
 pub struct Writer<W> {
     writer: W,
+    input_only: bool,
 }
 
 impl<W> Writer<W> {
     pub fn new(writer: W) -> Self {
-        Writer { writer }
+        Writer {
+            writer,
+            input_only: false,
+        }
+    }
+
+    /// Writer saving keystrokes instead of output, without the leading resize
+    /// sequence.
+    pub fn input_only(writer: W) -> Self {
+        Writer {
+            writer,
+            input_only: true,
+        }
     }
 }
 
+/// Reads terminal size from the resize sequence written at the beginning of
+/// raw recordings, returning it along with the length of the sequence.
+pub fn parse_size(data: &[u8]) -> Option<((u16, u16), usize)> {
+    let params = data.strip_prefix(b"\x1b[8;")?;
+    let end = params.iter().take(16).position(|&b| b == b't')?;
+    let (rows, cols) = std::str::from_utf8(&params[..end]).ok()?.split_once(';')?;
+
+    Some(((cols.parse().ok()?, rows.parse().ok()?), end + 5))
+}
+
 impl<W: Write> super::Writer for Writer<W> {
     fn header(&mut self, header: &super::Header) -> io::Result<()> {
+        if self.input_only {
+            return Ok(());
+        }
+
         write!(self.writer, "\x1b[8;{};{}t", header.rows, header.cols)
     }
 
     fn output(&mut self, _time: f64, data: &[u8]) -> io::Result<()> {
+        if self.input_only {
+            return Ok(());
+        }
+
         self.writer.write_all(data)
     }
 
-    fn input(&mut self, _time: f64, _data: &[u8]) -> io::Result<()> {
-        Ok(())
+    fn input(&mut self, _time: f64, data: &[u8]) -> io::Result<()> {
+        if self.input_only {
+            self.writer.write_all(data)
+        } else {
+            Ok(())
+        }
     }
 
     fn resize(&mut self, _time: f64, _size: (u16, u16)) -> io::Result<()> {
         Ok(())
     }
+
+    fn exit(&mut self, _time: f64, _status: i32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn marker(&mut self, _time: f64, _label: Option<&str>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn key(&mut self, _time: f64, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Writer;
+    use crate::format::{self, Writer as _};
+    use std::collections::HashMap;
+
+    #[test]
+    fn writer() {
+        let header = format::Header {
+            cols: 80,
+            rows: 24,
+            timestamp: 0,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: HashMap::new(),
+            cwd: None,
+            theme: None,
+            original_term: None,
+        };
+
+        let mut data = Vec::new();
+        let mut fw = Writer::new(&mut data);
+        fw.header(&header).unwrap();
+        fw.output(0.0, b"foo").unwrap();
+
+        assert_eq!(data, b"\x1b[8;24;80tfoo");
+        assert_eq!(super::parse_size(&data), Some(((80, 24), 10)));
+    }
+
+    #[test]
+    fn parse_size() {
+        assert_eq!(
+            super::parse_size(b"\x1b[8;24;80thello"),
+            Some(((80, 24), 10))
+        );
+
+        assert_eq!(super::parse_size(b"\x1b[8;24t"), None);
+        assert_eq!(super::parse_size(b"hello"), None);
+    }
 }
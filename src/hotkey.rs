@@ -0,0 +1,233 @@
+use crate::keys;
+use anyhow::bail;
+
+/// Parses a hotkey given as `C-x` (control + letter, or one of `@[\]^_`) or
+/// as a single ASCII character, returning the byte the terminal sends for it.
+pub fn parse(key: &str) -> anyhow::Result<u8> {
+    let byte = match key.as_bytes() {
+        [b'C' | b'c', b'-', c] if c.is_ascii_alphabetic() || b"@[\\]^_".contains(c) => {
+            c.to_ascii_uppercase() & 0x1f
+        }
+
+        [c] if c.is_ascii() => *c,
+
+        _ => bail!("invalid hotkey \"{}\", use e.g. C-x", key),
+    };
+
+    Ok(byte)
+}
+
+/// Picks presses of the recording hotkeys out of the input. After the marker
+/// hotkey the following keystrokes, up to <enter>, are taken as the marker's
+/// label instead of being passed to the recorded program. <ctrl-c> cancels
+/// the marker, backspace works as usual. The pause hotkey toggles recording.
+///
+/// Hotkeys are ignored inside a bracketed paste, so pasted text is passed on
+/// as is. The paste brackets, like everything else, may be split between
+/// reads from the tty.
+///
+/// Escape sequences, i.e. keys like <up> and replies of the terminal to
+/// queries of the recorded program (e.g. cursor position reports), are passed
+/// on whole, even while typing a label, and never taken for a hotkey. They're
+/// told apart the same way as in `keys::Decoder`, so they're expected to come
+/// in a single read too.
+#[derive(Default)]
+pub struct Hotkeys {
+    marker: Option<u8>,
+    pause: Option<u8>,
+    paste: keys::Paste,
+    label: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    /// Marker with its label, if one was typed.
+    Marker(Option<String>),
+    /// Pausing or resuming the recording.
+    Pause,
+}
+
+impl Hotkeys {
+    pub fn new(marker: Option<u8>, pause: Option<u8>) -> Self {
+        Self {
+            marker,
+            pause,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the input to pass on, and actions of the hotkeys pressed in it,
+    /// markers once their label is complete.
+    pub fn process(&mut self, data: &[u8]) -> (Vec<u8>, Vec<Action>) {
+        let mut input = Vec::with_capacity(data.len());
+        let mut actions = Vec::new();
+        // an ESC hotkey can't be told apart from sequences
+        let esc_hotkey = [self.marker, self.pause].contains(&Some(0x1b));
+        let mut rest = data;
+
+        while let [byte, ..] = *rest {
+            let len = match byte {
+                0x1b if !esc_hotkey => keys::key_len(rest),
+                _ => 1,
+            };
+
+            let (key, tail) = rest.split_at(len);
+            rest = tail;
+
+            if len > 1 {
+                if self.label.is_none() {
+                    for &byte in key {
+                        self.paste.feed(byte);
+                    }
+                }
+
+                input.extend_from_slice(key);
+                continue;
+            }
+
+            if let Some(label) = &mut self.label {
+                match byte {
+                    b'\r' | b'\n' => {
+                        let label = String::from_utf8_lossy(label).into_owned();
+                        let label = Some(label).filter(|label| !label.is_empty());
+                        actions.push(Action::Marker(label));
+                        self.label = None;
+                    }
+
+                    0x03 => self.label = None,
+
+                    0x08 | 0x7f => {
+                        while let Some(byte) = label.pop() {
+                            // drop continuation bytes until the leading one
+                            if byte & 0xc0 != 0x80 {
+                                break;
+                            }
+                        }
+                    }
+
+                    0x20.. => label.push(byte),
+
+                    _ => (),
+                }
+
+                continue;
+            }
+
+            if self.paste.feed(byte) {
+                input.push(byte);
+            } else if Some(byte) == self.marker {
+                self.label = Some(Vec::new());
+            } else if Some(byte) == self.pause {
+                actions.push(Action::Pause);
+            } else {
+                input.push(byte);
+            }
+        }
+
+        (input, actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Hotkeys};
+
+    #[test]
+    fn parse() {
+        use super::parse;
+
+        assert_eq!(parse("C-x").unwrap(), 0x18);
+        assert_eq!(parse("C-X").unwrap(), 0x18);
+        assert_eq!(parse("c-a").unwrap(), 0x01);
+        assert_eq!(parse("C-]").unwrap(), 0x1d);
+        assert_eq!(parse("~").unwrap(), b'~');
+        assert!(parse("C-1").is_err());
+        assert!(parse("xy").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn markers() {
+        let mut key = Hotkeys::new(Some(0x18), None);
+
+        assert_eq!(
+            key.process(b"ls\x18\r"),
+            (b"ls".to_vec(), vec![Action::Marker(None)])
+        );
+        assert_eq!(key.process(b"\x18int"), (vec![], vec![]));
+        assert_eq!(
+            key.process(b"ro\x7f\x7fro\r\r"),
+            (
+                b"\r".to_vec(),
+                vec![Action::Marker(Some("intro".to_owned()))]
+            )
+        );
+        assert_eq!(
+            key.process("\x18żó\x7f\r".as_bytes()),
+            (vec![], vec![Action::Marker(Some("ż".to_owned()))])
+        );
+        assert_eq!(key.process(b"\x18oops\x03q"), (b"q".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn pause() {
+        let mut keys = Hotkeys::new(Some(0x18), Some(0x10));
+
+        assert_eq!(
+            keys.process(b"a\x10b\x18x\x10\r\x10"),
+            (
+                b"ab".to_vec(),
+                vec![
+                    Action::Pause,
+                    Action::Marker(Some("x".to_owned())),
+                    Action::Pause
+                ]
+            )
+        );
+
+        let mut keys = Hotkeys::new(None, Some(0x10));
+
+        assert_eq!(
+            keys.process(b"\x18\x1b[200~\x10\x1b[201~"),
+            (b"\x18\x1b[200~\x10\x1b[201~".to_vec(), vec![])
+        );
+    }
+
+    #[test]
+    fn paste() {
+        let mut key = Hotkeys::new(Some(0x18), None);
+
+        assert_eq!(
+            key.process(b"\x1b[200~a\x18"),
+            (b"\x1b[200~a\x18".to_vec(), vec![])
+        );
+        assert_eq!(key.process(b"\x1b[20"), (b"\x1b[20".to_vec(), vec![]));
+        assert_eq!(
+            key.process(b"1~\x18\r"),
+            (b"1~".to_vec(), vec![Action::Marker(None)])
+        );
+    }
+
+    #[test]
+    fn terminal_replies() {
+        let mut key = Hotkeys::new(Some(b'R'), None);
+
+        // cursor position report, and <up>
+        assert_eq!(
+            key.process(b"\x1b[12;40R\x1b[A"),
+            (b"\x1b[12;40R\x1b[A".to_vec(), vec![])
+        );
+        assert_eq!(key.process(b"R\r"), (vec![], vec![Action::Marker(None)]));
+
+        // replies to queries made while typing a label still get through
+        let mut key = Hotkeys::new(Some(0x18), None);
+
+        assert_eq!(
+            key.process(b"\x18in\x1b[5;1R\x1b]11;rgb:0000/0000/0000\x07tro\r"),
+            (
+                b"\x1b[5;1R\x1b]11;rgb:0000/0000/0000\x07".to_vec(),
+                vec![Action::Marker(Some("intro".to_owned()))]
+            )
+        );
+    }
+}
@@ -0,0 +1,316 @@
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Turns input read from the terminal into names of the pressed keys, e.g.
+/// `a`, `Enter`, `C-c` or `C-Up`, in the notation used for hotkeys.
+///
+/// Terminals send a key's escape sequence in one go, so sequences are
+/// expected not to be split between reads, except for bracketed paste: a
+/// paste is named once, as `Paste`, with the pasted text skipped however many
+/// reads it spans.
+///
+/// Replies of the terminal to queries made by the recorded program, like
+/// cursor position reports or device attributes, come in the same input as
+/// keys. They aren't keys, so they get no name.
+#[derive(Default)]
+pub struct Decoder {
+    paste: Paste,
+}
+
+impl Decoder {
+    pub fn decode(&mut self, data: &[u8]) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut rest = data;
+
+        while !rest.is_empty() {
+            if self.paste.is_active() {
+                self.paste.feed(rest[0]);
+                rest = &rest[1..];
+                continue;
+            }
+
+            let (name, len) = decode_key(rest);
+
+            for &byte in &rest[..len] {
+                self.paste.feed(byte);
+            }
+
+            if self.paste.is_active() {
+                names.push("Paste".to_owned());
+            } else {
+                names.extend(name);
+            }
+
+            rest = &rest[len..];
+        }
+
+        names
+    }
+}
+
+/// Follows bracketed paste in the input byte by byte, so the brackets may be
+/// split between reads.
+#[derive(Default)]
+pub struct Paste {
+    active: bool,
+    matched: usize,
+}
+
+impl Paste {
+    /// Moves past `byte`, telling whether it's in a paste, the brackets
+    /// included.
+    pub fn feed(&mut self, byte: u8) -> bool {
+        let bracket = if self.active { PASTE_END } else { PASTE_START };
+
+        if byte == bracket[self.matched] {
+            self.matched += 1;
+
+            if self.matched == bracket.len() {
+                self.active = !self.active;
+                self.matched = 0;
+
+                return true;
+            }
+        } else {
+            self.matched = usize::from(byte == bracket[0]);
+        }
+
+        self.active
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// Length of the first key in `data`, e.g. of its whole escape sequence, or
+/// of a terminal's reply to a query.
+pub fn key_len(data: &[u8]) -> usize {
+    decode_key(data).1
+}
+
+/// Decodes the first key in `data`, returning its name, if it's a known key,
+/// and the length of its sequence.
+fn decode_key(data: &[u8]) -> (Option<String>, usize) {
+    match data {
+        [0x1b, b'[', rest @ ..] => csi(rest).map_or((None, 2), |(name, len)| (name, len + 2)),
+        [0x1b, b'O', c, ..] => (ss3(*c).map(String::from), 3),
+        [0x1b, b']' | b'P' | b'X' | b'^' | b'_', ..] if string_end(data).is_some() => {
+            (None, string_end(data).unwrap_or_default())
+        }
+        [0x1b, 0x1b, ..] => (Some("Esc".to_owned()), 1),
+        // ESC followed by a control key other than <enter> or <tab> is more
+        // likely a lone ESC than meta
+        [0x1b, b, ..] if *b >= 0x20 || *b == b'\r' || *b == b'\t' => {
+            let (name, len) = decode_key(&data[1..]);
+            (name.map(|name| format!("M-{}", name)), len + 1)
+        }
+        [byte, ..] => control(*byte).map_or_else(|| char(data), |name| (Some(name), 1)),
+        [] => (None, 0),
+    }
+}
+
+fn control(byte: u8) -> Option<String> {
+    let name = match byte {
+        b'\r' => "Enter",
+        b'\t' => "Tab",
+        0x1b => "Esc",
+        0x7f => "Backspace",
+        b' ' => "Space",
+        0x00 => "C-@",
+        0x01..=0x1a => return Some(format!("C-{}", (b'a' + byte - 1) as char)),
+        0x1c..=0x1f => return Some(format!("C-{}", (byte + 0x40) as char)),
+        _ => return None,
+    };
+
+    Some(name.to_owned())
+}
+
+/// A printable character, or a byte which isn't part of valid UTF-8, named
+/// by nothing.
+fn char(data: &[u8]) -> (Option<String>, usize) {
+    let len = match data[0] {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    };
+
+    match data.get(..len).map(std::str::from_utf8) {
+        Some(Ok(c)) => (Some(c.to_owned()), len),
+        _ => (None, 1),
+    }
+}
+
+/// Length of an OSC, DCS, SOS, PM or APC string, e.g. a reply to a query
+/// of the background color, up to and including its terminator.
+fn string_end(data: &[u8]) -> Option<usize> {
+    let bel = data[0] == 0x1b && data[1] == b']';
+
+    (2..data.len()).find_map(|i| match &data[i..] {
+        [0x07, ..] if bel => Some(i + 1),
+        [0x1b, b'\\', ..] => Some(i + 2),
+        _ => None,
+    })
+}
+
+/// CSI sequence, given without the leading `ESC [`, e.g. `1;5A` for C-Up.
+fn csi(data: &[u8]) -> Option<(Option<String>, usize)> {
+    let end = data.iter().position(|b| (0x40..=0x7e).contains(b))?;
+    let params = std::str::from_utf8(&data[..end]).ok()?;
+
+    // private parameters are only found in replies, e.g. `?62;22c` to
+    // a device attributes query
+    if params.starts_with(['?', '>', '<', '=']) {
+        return Some((None, end + 1));
+    }
+
+    let mut params = params.split(';');
+    let first = params.next().unwrap_or_default();
+    let modifier = params.next();
+
+    // a cursor position report, `row;col R`, looks like a modified F3,
+    // which only ever has a row of 1
+    if data[end] == b'R' && (!matches!(first, "" | "1") || !is_modifier(modifier)) {
+        return Some((None, end + 1));
+    }
+
+    let modifier = modifier.and_then(|m| m.parse::<u8>().ok());
+
+    let key = match (data[end], first) {
+        (b'A', _) => Some("Up"),
+        (b'B', _) => Some("Down"),
+        (b'C', _) => Some("Right"),
+        (b'D', _) => Some("Left"),
+        (b'H', _) => Some("Home"),
+        (b'F', _) => Some("End"),
+        (b'Z', _) => Some("S-Tab"),
+        (b'P'..=b'S', _) => ss3(data[end]),
+        (b'~', "1" | "7") => Some("Home"),
+        (b'~', "2") => Some("Insert"),
+        (b'~', "3") => Some("Delete"),
+        (b'~', "4" | "8") => Some("End"),
+        (b'~', "5") => Some("PageUp"),
+        (b'~', "6") => Some("PageDown"),
+        (b'~', "15") => Some("F5"),
+        (b'~', "17") => Some("F6"),
+        (b'~', "18") => Some("F7"),
+        (b'~', "19") => Some("F8"),
+        (b'~', "20") => Some("F9"),
+        (b'~', "21") => Some("F10"),
+        (b'~', "23") => Some("F11"),
+        (b'~', "24") => Some("F12"),
+        _ => None,
+    };
+
+    let name = key.map(|key| format!("{}{}", modifiers(modifier), key));
+
+    Some((name, end + 1))
+}
+
+/// Keys sent as `ESC O` and a letter, e.g. in application cursor mode.
+fn ss3(c: u8) -> Option<&'static str> {
+    match c {
+        b'A' => Some("Up"),
+        b'B' => Some("Down"),
+        b'C' => Some("Right"),
+        b'D' => Some("Left"),
+        b'H' => Some("Home"),
+        b'F' => Some("End"),
+        b'P' => Some("F1"),
+        b'Q' => Some("F2"),
+        b'R' => Some("F3"),
+        b'S' => Some("F4"),
+        _ => None,
+    }
+}
+
+fn is_modifier(param: Option<&str>) -> bool {
+    param.is_none_or(|m| matches!(m.parse::<u8>(), Ok(1..=16)))
+}
+
+/// Modifier parameter of xterm's key sequences is 1 + a bit mask of shift
+/// (1), alt (2) and control (4).
+fn modifiers(param: Option<u8>) -> String {
+    let mask = param.unwrap_or(1).saturating_sub(1);
+    let mut prefix = String::new();
+
+    if mask & 4 != 0 {
+        prefix.push_str("C-");
+    }
+
+    if mask & 2 != 0 {
+        prefix.push_str("M-");
+    }
+
+    if mask & 1 != 0 {
+        prefix.push_str("S-");
+    }
+
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+
+    fn decode(data: &[u8]) -> Vec<String> {
+        Decoder::default().decode(data)
+    }
+
+    #[test]
+    fn printable() {
+        assert_eq!(decode(b"ls -l"), ["l", "s", "Space", "-", "l"]);
+        assert_eq!(decode("zł".as_bytes()), ["z", "ł"]);
+        assert_eq!(decode(b"\xff"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn control() {
+        assert_eq!(
+            decode(b"\x03\r\t\x7f\x1b\x00\x1d"),
+            ["C-c", "Enter", "Tab", "Backspace", "Esc", "C-@", "C-]"]
+        );
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(
+            decode(b"\x1b[A\x1bOB\x1b[1;5C\x1b[3~\x1b[5;2~\x1b[Z\x1bOP\x1b[15~"),
+            ["Up", "Down", "C-Right", "Delete", "S-PageUp", "S-Tab", "F1", "F5"]
+        );
+        assert_eq!(decode(b"\x1bx\x1b\r"), ["M-x", "M-Enter"]);
+        assert_eq!(decode(b"\x1b[?1;2c!"), ["!"]);
+        assert_eq!(decode(b"\x1b\x1b[A"), ["Esc", "Up"]);
+    }
+
+    #[test]
+    fn terminal_replies() {
+        // cursor position report
+        assert!(decode(b"\x1b[12;40R").is_empty());
+        assert!(decode(b"\x1b[1;300R").is_empty());
+        assert_eq!(decode(b"\x1b[1;5R\x1bOR"), ["C-F3", "F3"]);
+
+        assert_eq!(
+            decode(b"a\x1b[?62;22c\x1b]11;rgb:0000/0000/0000\x07\x1bP1$r0m\x1b\\b"),
+            ["a", "b"]
+        );
+
+        // not a reply without the terminator
+        assert_eq!(decode(b"\x1b]"), ["M-]"]);
+    }
+
+    #[test]
+    fn paste() {
+        let mut decoder = Decoder::default();
+
+        assert_eq!(decoder.decode(b"a\x1b[200~echo"), ["a", "Paste"]);
+        assert!(decoder.decode(b" hi\r").is_empty());
+        assert_eq!(decoder.decode(b"\x1b[201~\r"), ["Enter"]);
+
+        // closing bracket split between reads
+        assert_eq!(decoder.decode(b"\x1b[200~x\x1b[2"), ["Paste"]);
+        assert!(decoder.decode(b"01~").is_empty());
+        assert_eq!(decoder.decode(b"y"), ["y"]);
+    }
+}
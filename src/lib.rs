@@ -0,0 +1,45 @@
+//! Recording and playback of terminal sessions, as done by the `asciinema`
+//! CLI, for embedding in other programs.
+//!
+//! `record` runs a command in a pty and writes the session to a sink as an
+//! asciicast v2 recording. For more control, a command is run with `exec` into
+//! a `Recorder`, which writes events with e.g. a `Writer`. Recordings are
+//! replayed with a `Player`.
+
+mod cat;
+mod cli;
+mod config;
+mod convert;
+mod edit;
+mod environ;
+mod format;
+mod hotkey;
+mod keys;
+mod locale;
+mod ls;
+mod markers;
+mod meta;
+mod notifier;
+mod output;
+mod player;
+mod pty;
+mod rec;
+mod recorder;
+mod render;
+mod snapshot;
+mod socket;
+mod tty;
+mod vt;
+
+#[cfg(test)]
+mod test_util;
+
+pub use format::asciicast::{Theme, Writer};
+pub use player::{Loop, Player};
+pub use pty::{exec, Dimension, Exit, ExitStatus};
+pub use recorder::{
+    record, Clock, Metadata, Metrics, Options, Recorder, Settings, Summary, SystemClock,
+};
+
+#[doc(hidden)]
+pub use cli::main as cli_main;
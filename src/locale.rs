@@ -1,8 +1,23 @@
 use nix::libc::{self, CODESET, LC_ALL};
 use std::env;
 use std::ffi::CStr;
+use std::fmt::{self, Display};
 
-pub fn check_utf8_locale() -> anyhow::Result<()> {
+#[derive(Debug)]
+pub struct UnsupportedLocale {
+    env: String,
+    encoding: String,
+}
+
+impl Display for UnsupportedLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "asciinema requires ASCII or UTF-8 character encoding. The environment ({}) specifies the character set \"{}\". Check the output of `locale` command.", self.env, self.encoding)
+    }
+}
+
+impl std::error::Error for UnsupportedLocale {}
+
+pub fn check_utf8_locale() -> Result<(), UnsupportedLocale> {
     initialize_from_env();
 
     let encoding = get_encoding();
@@ -16,10 +31,11 @@ pub fn check_utf8_locale() -> anyhow::Result<()> {
             .or(env::var("LANG").map(|v| format!("LANG={}", v)))
             .unwrap_or("".to_string());
 
-        Err(anyhow::anyhow!("asciinema requires ASCII or UTF-8 character encoding. The environment ({}) specifies the character set \"{}\". Check the output of `locale` command.", env, encoding))
+        Err(UnsupportedLocale { env, encoding })
     }
 }
 
+#[allow(clippy::manual_c_str_literals)]
 pub fn initialize_from_env() {
     unsafe {
         libc::setlocale(LC_ALL, b"\0".as_ptr() as *const libc::c_char);
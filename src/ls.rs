@@ -0,0 +1,296 @@
+use crate::format::{asciicast, raw};
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// How much of the end of a recording gets read for its duration.
+const TAIL_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Sort {
+    Date,
+    Duration,
+    Name,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub filename: String,
+    pub duration: Option<f64>,
+    pub cols: u16,
+    pub rows: u16,
+    pub title: Option<String>,
+    pub timestamp: u64,
+}
+
+pub fn run<P: AsRef<Path>>(dir: P, json: bool, sort: Sort) -> anyhow::Result<()> {
+    let entries = list(dir, sort)?;
+    let mut stdout = io::stdout().lock();
+
+    if json {
+        serde_json::to_writer(&mut stdout, &entries)?;
+        println!();
+    } else {
+        print_table(&mut stdout, &entries)?;
+    }
+
+    Ok(())
+}
+
+/// Lists recordings in `dir`, reading only their headers, and the ends of
+/// uncompressed asciicast v2 ones for the duration, so that big recordings
+/// don't slow it down.
+pub fn list<P: AsRef<Path>>(dir: P, sort: Sort) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+
+        if !path.is_file() {
+            continue;
+        }
+
+        if let Ok(Some(entry)) = read_entry(&path) {
+            entries.push(entry);
+        }
+    }
+
+    match sort {
+        Sort::Date => entries.sort_by_key(|e| e.timestamp),
+        Sort::Duration => entries.sort_by(|a, b| {
+            let duration = |e: &Entry| e.duration.unwrap_or(f64::NEG_INFINITY);
+
+            duration(a)
+                .total_cmp(&duration(b))
+                .then_with(|| a.filename.cmp(&b.filename))
+        }),
+        Sort::Name => entries.sort_by(|a, b| a.filename.cmp(&b.filename)),
+    }
+
+    Ok(entries)
+}
+
+fn read_entry(path: &Path) -> anyhow::Result<Option<Entry>> {
+    let filename = path.file_name().unwrap().to_string_lossy().to_string();
+    let file = fs::File::open(path)?;
+
+    if filename.ends_with(".cast") {
+        let mut reader = io::BufReader::new(file);
+        let (mut entry, version) = read_header(filename, &mut reader)?;
+
+        if version == Some(2) {
+            entry.duration = Some(last_event_time(reader.into_inner())?);
+        }
+
+        Ok(Some(entry))
+    } else if filename.ends_with(".cast.gz") {
+        // the duration would take decompressing it whole
+        let reader = io::BufReader::new(GzDecoder::new(file));
+        read_header(filename, reader).map(|(entry, _)| Some(entry))
+    } else {
+        read_raw(filename, file)
+    }
+}
+
+/// Reads the header of an asciicast recording, telling its version too.
+fn read_header<R: BufRead>(
+    filename: String,
+    mut reader: R,
+) -> anyhow::Result<(Entry, Option<u64>)> {
+    let mut first_line = String::new();
+    reader.read_line(&mut first_line)?;
+
+    let version = serde_json::from_str::<serde_json::Value>(&first_line)
+        .ok()
+        .and_then(|header| header["version"].as_u64());
+
+    // v1 is a single JSON document, so it gets read whole
+    let (header, _events) = asciicast::open(first_line.as_bytes().chain(reader))?;
+
+    let entry = Entry {
+        filename,
+        duration: None,
+        cols: header.cols,
+        rows: header.rows,
+        title: header.title,
+        timestamp: header.timestamp,
+    };
+
+    Ok((entry, version))
+}
+
+/// Time of the latest event among the ones at the end of an asciicast v2
+/// recording, 0 when there are none.
+fn last_event_time(mut file: fs::File) -> anyhow::Result<f64> {
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(TAIL_SIZE);
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let tail = String::from_utf8_lossy(&tail);
+    let mut lines = tail.lines();
+
+    // most likely starts in the middle of a line
+    if start > 0 {
+        lines.next();
+    }
+
+    let time = lines
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|event| event.get(0).and_then(serde_json::Value::as_f64))
+        .fold(0.0, f64::max);
+
+    Ok(time)
+}
+
+/// Raw recordings have no header, only the resize sequence written by
+/// `raw::Writer` at the beginning, so that's what identifies them.
+fn read_raw(filename: String, file: fs::File) -> anyhow::Result<Option<Entry>> {
+    let timestamp = file
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+
+    let mut prefix = Vec::new();
+    file.take(32).read_to_end(&mut prefix)?;
+
+    let size = raw::parse_size(&prefix).map(|(size, _)| size);
+
+    Ok(size.map(|(cols, rows)| Entry {
+        filename,
+        duration: None,
+        cols,
+        rows,
+        title: None,
+        timestamp,
+    }))
+}
+
+fn print_table<W: io::Write>(w: &mut W, entries: &[Entry]) -> io::Result<()> {
+    let rows = entries
+        .iter()
+        .map(|e| {
+            [
+                e.filename.clone(),
+                e.duration.map_or("-".to_owned(), format_duration),
+                format!("{}x{}", e.cols, e.rows),
+                e.title.clone().unwrap_or_default(),
+                format_date(e.timestamp),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["FILENAME", "DURATION", "SIZE", "TITLE", "RECORDED AT"].map(String::from);
+    let mut widths = header.clone().map(|h| h.chars().count());
+
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        writeln!(w, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// Formats epoch seconds as UTC date and time.
+fn format_date(timestamp: u64) -> String {
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86400) as i64 + 719468;
+    let secs = timestamp % 86400;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sort;
+
+    fn filenames(sort: Sort) -> Vec<String> {
+        super::list("tests/ls", sort)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.filename)
+            .collect()
+    }
+
+    #[test]
+    fn list() {
+        let entries = super::list("tests/ls", Sort::Name).unwrap();
+
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].filename, "long.cast.gz");
+        assert_eq!(entries[0].duration, None);
+        assert_eq!((entries[0].cols, entries[0].rows), (100, 40));
+        assert_eq!(entries[0].title.as_deref(), Some("Long one"));
+        assert_eq!(entries[0].timestamp, 1600000000);
+
+        assert_eq!(entries[1].filename, "output.raw");
+        assert_eq!(entries[1].duration, None);
+        assert_eq!((entries[1].cols, entries[1].rows), (120, 30));
+
+        assert_eq!(entries[2].filename, "short.cast");
+        assert_eq!(entries[2].duration, Some(1.5));
+        assert_eq!((entries[2].cols, entries[2].rows), (80, 24));
+        assert_eq!(entries[2].title, None);
+        assert_eq!(entries[2].timestamp, 1700000000);
+    }
+
+    #[test]
+    fn sort() {
+        assert_eq!(
+            filenames(Sort::Duration),
+            vec!["long.cast.gz", "output.raw", "short.cast"]
+        );
+
+        assert_eq!(
+            filenames(Sort::Date),
+            vec!["long.cast.gz", "short.cast", "output.raw"]
+        );
+    }
+
+    #[test]
+    fn format_date() {
+        assert_eq!(super::format_date(0), "1970-01-01 00:00");
+        assert_eq!(super::format_date(1700000000), "2023-11-14 22:13");
+    }
+}
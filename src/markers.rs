@@ -0,0 +1,122 @@
+use crate::format::asciicast::{self, EventCode};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// Time and label of each marker, tab separated
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Marker {
+    pub time: f64,
+    pub label: String,
+}
+
+/// Prints markers of the recording, one per line, nothing if there are none.
+/// `-` reads the recording from stdin.
+pub fn run(filename: &str, format: Format, max_events: Option<usize>) -> anyhow::Result<()> {
+    let reader: Box<dyn BufRead> = match filename {
+        "-" => Box::new(io::stdin().lock()),
+        _ => Box::new(io::BufReader::new(fs::File::open(filename)?)),
+    };
+
+    let mut stdout = io::stdout().lock();
+
+    for marker in list(reader, max_events)? {
+        print(&mut stdout, &marker, format)?;
+    }
+
+    Ok(())
+}
+
+pub fn list<R: BufRead>(reader: R, max_events: Option<usize>) -> anyhow::Result<Vec<Marker>> {
+    let (_header, events) = asciicast::open(reader)?;
+    let mut markers = Vec::new();
+
+    for event in asciicast::limit(events, max_events) {
+        let event = event?;
+
+        if event.code == EventCode::Marker {
+            markers.push(Marker {
+                time: event.time,
+                // the asciicast v2 spec has the label as the data
+                label: event.label.unwrap_or(event.data),
+            });
+        }
+    }
+
+    Ok(markers)
+}
+
+fn print<W: Write>(sink: &mut W, marker: &Marker, format: Format) -> anyhow::Result<()> {
+    match format {
+        Format::Text => writeln!(sink, "{:.3}\t{}", marker.time, marker.label)?,
+
+        Format::Json => {
+            serde_json::to_writer(&mut *sink, marker)?;
+            writeln!(sink)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Marker};
+
+    #[test]
+    fn list() {
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+[1.0, \"o\", \"$ \"]\n\
+[1.5, \"m\", \"\"]\n\
+[2.25, \"m\", \"\", \"intro\"]\n\
+[3.0, \"m\", \"legacy\"]\n";
+
+        let markers = super::list(&data[..], None).unwrap();
+
+        assert_eq!(
+            markers,
+            vec![
+                Marker {
+                    time: 1.5,
+                    label: "".to_owned()
+                },
+                Marker {
+                    time: 2.25,
+                    label: "intro".to_owned()
+                },
+                Marker {
+                    time: 3.0,
+                    label: "legacy".to_owned()
+                },
+            ]
+        );
+
+        let data = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"$ \"]\n";
+
+        assert!(super::list(&data[..], None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn print() {
+        let marker = Marker {
+            time: 2.25,
+            label: "intro".to_owned(),
+        };
+
+        let mut output = Vec::new();
+        super::print(&mut output, &marker, Format::Text).unwrap();
+        super::print(&mut output, &marker, Format::Json).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "2.250\tintro\n{\"time\":2.25,\"label\":\"intro\"}\n"
+        );
+    }
+}
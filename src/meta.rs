@@ -0,0 +1,134 @@
+use crate::recorder::Metrics;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize)]
+pub struct Meta {
+    version: String,
+    duration: f64,
+    cols: u16,
+    rows: u16,
+    events: BTreeMap<String, usize>,
+    env: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_term: Option<String>,
+    exit_status: i32,
+}
+
+impl Meta {
+    pub fn new<'a, I: IntoIterator<Item = &'a String>>(
+        metrics: &Metrics,
+        env: I,
+        original_term: Option<&str>,
+        exit_status: i32,
+    ) -> Self {
+        let mut events = BTreeMap::new();
+        events.insert("o".to_owned(), metrics.output_events);
+        events.insert("i".to_owned(), metrics.input_events);
+        events.insert("r".to_owned(), metrics.resize_events);
+        events.insert("m".to_owned(), metrics.marker_events);
+        events.insert("x".to_owned(), metrics.exit_events);
+        events.insert("k".to_owned(), metrics.key_events);
+
+        let mut env = env.into_iter().cloned().collect::<Vec<_>>();
+        env.sort();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            duration: metrics.duration,
+            cols: metrics.cols,
+            rows: metrics.rows,
+            events,
+            env,
+            original_term: original_term.map(String::from),
+            exit_status,
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+pub fn path_for<P: AsRef<Path>>(recording_path: P) -> PathBuf {
+    let mut path = recording_path.as_ref().as_os_str().to_owned();
+    path.push(".meta.json");
+
+    path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Meta;
+    use crate::format::asciicast;
+    use crate::pty::{ExitStatus, Recorder as _};
+    use crate::recorder::{Metadata, Recorder, Settings, SystemClock};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn matches_recording() {
+        let dir = crate::test_util::TempDir::new();
+        let path = dir.join("demo.cast");
+        let file = fs::File::create(&path).unwrap();
+
+        let mut env = HashMap::new();
+        env.insert("TERM".to_owned(), "xterm-256color".to_owned());
+        env.insert("SHELL".to_owned(), "/bin/bash".to_owned());
+
+        let mut recorder = Recorder::new(
+            Box::new(asciicast::Writer::new(file, 0.0)),
+            Settings {
+                record_input: true,
+                ..Default::default()
+            },
+            Metadata {
+                env,
+                ..Default::default()
+            },
+            Box::new(SystemClock),
+        );
+
+        recorder.start((100, 30)).unwrap();
+        recorder.output(b"$ ");
+        recorder.input(b"ls\r", false);
+        recorder.output(b"ls\r\n");
+        recorder.resize((120, 40));
+        recorder.output(b"demo.cast\r\n");
+        recorder.marker(Some("done"));
+        recorder.record_exit(ExitStatus::Exited(3));
+
+        recorder.flush();
+        let meta = Meta::new(recorder.metrics(), recorder.env().keys(), None, 3);
+        meta.write(super::path_for(&path)).unwrap();
+        drop(recorder);
+
+        let sidecar: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("demo.cast.meta.json")).unwrap())
+                .unwrap();
+
+        let (header, events) =
+            asciicast::open(io::BufReader::new(fs::File::open(&path).unwrap())).unwrap();
+
+        let events = events.collect::<anyhow::Result<Vec<_>>>().unwrap();
+        let count = |s: &str| events.iter().filter(|e| e.code.to_string() == s).count();
+
+        assert_eq!(sidecar["cols"], header.cols);
+        assert_eq!(sidecar["rows"], header.rows);
+        assert_eq!(sidecar["duration"], events.last().unwrap().time);
+        assert_eq!(sidecar["events"]["o"], count("o"));
+        assert_eq!(sidecar["events"]["i"], count("i"));
+        assert_eq!(sidecar["events"]["r"], count("r"));
+        assert_eq!(sidecar["events"]["m"], count("m"));
+        assert_eq!(sidecar["events"]["x"], count("x"));
+        assert_eq!(sidecar["events"]["k"], count("k"));
+        assert_eq!(sidecar["env"], serde_json::json!(["SHELL", "TERM"]));
+        assert_eq!(sidecar["exit_status"], 3);
+        assert!(sidecar.get("original_term").is_none());
+        assert_eq!(sidecar["version"], env!("CARGO_PKG_VERSION"));
+    }
+}
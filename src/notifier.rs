@@ -0,0 +1,55 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
+/// Prints informational messages for the user, unless told to be quiet.
+pub struct Notifier<W: Write> {
+    sink: W,
+    quiet: bool,
+}
+
+impl Notifier<io::Stderr> {
+    pub fn stderr(quiet: bool) -> Self {
+        Self::new(io::stderr(), quiet)
+    }
+}
+
+impl<W: Write> Notifier<W> {
+    pub fn new(sink: W, quiet: bool) -> Self {
+        Self { sink, quiet }
+    }
+
+    pub fn notice<D: Display>(&mut self, message: D) {
+        if !self.quiet {
+            let _ = writeln!(self.sink, "asciinema: {}", message);
+        }
+    }
+
+    pub fn warning<D: Display>(&mut self, message: D) {
+        self.notice(format_args!("warning: {}", message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Notifier;
+
+    #[test]
+    fn quiet() {
+        let mut output = Vec::new();
+        let mut notifier = Notifier::new(&mut output, false);
+        notifier.notice("recording finished");
+        notifier.warning("low disk space");
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "asciinema: recording finished\nasciinema: warning: low disk space\n"
+        );
+
+        let mut output = Vec::new();
+        let mut notifier = Notifier::new(&mut output, true);
+        notifier.notice("recording finished");
+        notifier.warning("low disk space");
+
+        assert!(output.is_empty());
+    }
+}
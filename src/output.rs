@@ -0,0 +1,351 @@
+use anyhow::{bail, Context, Result};
+use nix::libc;
+use std::fs;
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Output command's process, which gets waited for when dropped, so that
+/// asciinema doesn't exit before the command is done with what it was given,
+/// even when recording fails midway.
+pub struct OutputChild(process::Child);
+
+impl OutputChild {
+    /// Takes the command's stdin, closing which lets the command finish.
+    pub fn take_stdin(&mut self) -> process::ChildStdin {
+        self.0.stdin.take().unwrap()
+    }
+
+    pub fn wait(&mut self) -> io::Result<process::ExitStatus> {
+        self.0.wait()
+    }
+}
+
+impl Drop for OutputChild {
+    fn drop(&mut self) {
+        // closed first, in case it hasn't been taken
+        self.0.stdin = None;
+        let _ = self.0.wait();
+    }
+}
+
+pub fn spawn_output_command(command: &str) -> Result<OutputChild> {
+    process::Command::new("/bin/sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(process::Stdio::piped())
+        .spawn()
+        .map(OutputChild)
+        .with_context(|| format!("can't start output command `{}`", command))
+}
+
+/// Opens the recording file. Appending to a missing or empty file is the same
+/// as starting a fresh recording, so the returned flag tells whether the
+/// recording really continues an existing one.
+pub fn open_output_file(
+    filename: &str,
+    mut append: bool,
+    mut overwrite: bool,
+    follow_symlinks: bool,
+) -> Result<(fs::File, bool)> {
+    let path = Path::new(filename);
+
+    if path.exists() {
+        let metadata = fs::metadata(path)?;
+
+        if metadata.len() == 0 {
+            overwrite = true;
+            append = false;
+        }
+        // TODO if !append && !overwrite - error message
+    } else {
+        append = false;
+    }
+
+    let mut options = fs::OpenOptions::new();
+
+    options
+        .write(true)
+        .append(append)
+        .create(overwrite)
+        .create_new(!overwrite && !append)
+        .truncate(overwrite);
+
+    if !follow_symlinks {
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+
+    let file = options.open(filename).map_err(|e| {
+        if e.raw_os_error() == Some(libc::ELOOP) {
+            anyhow::anyhow!("{} is a symlink, refusing to follow it", filename)
+        } else {
+            e.into()
+        }
+    })?;
+
+    Ok((file, append))
+}
+
+/// Opens a temporary file next to `filename` to record to, to be renamed to
+/// it once the recording is complete. Returns the file, its path and the path
+/// to rename it to.
+pub fn open_temp_file(
+    filename: &str,
+    overwrite: bool,
+    follow_symlinks: bool,
+) -> Result<(fs::File, PathBuf, PathBuf)> {
+    let mut path = PathBuf::from(filename);
+
+    if path.is_symlink() {
+        if !follow_symlinks {
+            bail!("{} is a symlink, refusing to follow it", filename);
+        }
+
+        // renaming onto the symlink would replace the link itself
+        path = fs::canonicalize(&path)?;
+    }
+
+    // an empty file may be replaced, the same as without a temporary file
+    if !overwrite && fs::metadata(&path).is_ok_and(|m| m.len() > 0) {
+        bail!("{} already exists, use --overwrite to replace it", filename);
+    }
+
+    let mut tmp_path = path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    // whatever is there is a leftover of an interrupted recording
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(&tmp_path)
+        .with_context(|| format!("can't create {}", tmp_path.display()))?;
+
+    Ok((file, tmp_path, path))
+}
+
+/// Makes sure the recording being appended to is asciicast v2, the version
+/// the events get written in. Files which aren't asciicast at all, i.e. raw
+/// recordings, are appended to as they always were. A size differing from
+/// the terminal's is fine, the appended part starts with a resize then.
+pub fn check_appendable(filename: &str) -> Result<()> {
+    let file = io::BufReader::new(fs::File::open(filename)?);
+
+    // the header line, or the whole document in case of v1
+    let header = serde_json::Deserializer::from_reader(file)
+        .into_iter::<serde_json::Value>()
+        .next();
+
+    match header
+        .and_then(Result::ok)
+        .and_then(|h| h.get("version").cloned())
+    {
+        Some(version) if version != 2 => bail!(
+            "{} is an asciicast v{} recording, only v2 ones can be appended to",
+            filename,
+            version
+        ),
+
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::asciicast;
+    use crate::pty::Recorder as _;
+    use crate::recorder::{Recorder, Settings, SystemClock};
+    use crate::test_util::TempDir;
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn append_to_empty_file() {
+        let dir = TempDir::new();
+        let path = dir.join("empty.cast");
+        fs::File::create(&path).unwrap();
+
+        let (file, append) =
+            super::open_output_file(path.to_str().unwrap(), true, false, true).unwrap();
+
+        assert!(!append);
+
+        let writer = asciicast::Writer::new(file, 0.0);
+        let mut recorder = Recorder::new(
+            Box::new(writer),
+            Settings {
+                append,
+                ..Default::default()
+            },
+            Default::default(),
+            Box::new(SystemClock),
+        );
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"hello");
+        drop(recorder);
+
+        let (header, events) =
+            asciicast::open(io::BufReader::new(fs::File::open(&path).unwrap())).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<_>>>().unwrap();
+
+        assert_eq!((header.cols, header.rows), (80, 24));
+        assert_eq!(events.len(), 1);
+        assert!(events[0].time < 1.0);
+        assert_eq!(events[0].data, "hello");
+    }
+
+    #[test]
+    fn check_appendable() {
+        let dir = TempDir::new();
+        let path = dir.join("demo.cast");
+        let path = path.to_str().unwrap();
+
+        let check = |content: &str| {
+            fs::write(path, content).unwrap();
+            super::check_appendable(path)
+        };
+
+        assert!(
+            check("{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\", \"hi\"]\n")
+                .is_ok()
+        );
+        assert!(check("{\"version\": 2, \"width\": 100, \"height\": 30}\n").is_ok());
+        assert!(check("$ ls\r\n").is_ok());
+
+        let error = check("{\"version\": 3, \"term\": {\"cols\": 80, \"rows\": 24}}\n")
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.ends_with("is an asciicast v3 recording, only v2 ones can be appended to"));
+
+        assert!(check(
+            "{\n  \"version\": 1,\n  \"width\": 80,\n  \"height\": 24,\n  \"stdout\": []\n}\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn timestamp() {
+        let dir = TempDir::new();
+        let path = dir.join("demo.cast");
+        let path = path.to_str().unwrap();
+
+        let record = |append: bool| {
+            let (file, append) = super::open_output_file(path, append, !append, true).unwrap();
+            let time_offset = if append {
+                asciicast::get_duration(path).unwrap()
+            } else {
+                0.0
+            };
+            let writer = asciicast::Writer::new(file, time_offset);
+            let mut recorder = Recorder::new(
+                Box::new(writer),
+                Settings {
+                    append,
+                    ..Default::default()
+                },
+                Default::default(),
+                Box::new(SystemClock),
+            );
+            recorder.start((80, 24)).unwrap();
+            recorder.output(b"hello");
+        };
+
+        let timestamp = || {
+            asciicast::open(io::BufReader::new(fs::File::open(path).unwrap()))
+                .unwrap()
+                .0
+                .timestamp
+        };
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        record(false);
+
+        assert!((now..now + 60).contains(&timestamp()));
+
+        fs::write(
+            path,
+            "{\"version\": 2, \"width\": 80, \"height\": 24, \"timestamp\": 1500000000}\n",
+        )
+        .unwrap();
+
+        record(true);
+
+        assert_eq!(timestamp(), 1500000000);
+    }
+
+    #[test]
+    fn no_follow_symlinks() {
+        let dir = TempDir::new();
+        let target = dir.join("target.cast");
+        let link = dir.join("link.cast");
+        fs::write(&target, "").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let link = link.to_str().unwrap();
+
+        let result = super::open_output_file(link, false, true, false);
+
+        assert!(result.unwrap_err().to_string().contains("symlink"));
+        assert!(super::open_output_file(link, false, true, true).is_ok());
+    }
+
+    #[test]
+    fn temp_file() {
+        let dir = TempDir::new();
+        let target = dir.join("demo.cast");
+        let link = dir.join("link.cast");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let filename = target.to_str().unwrap();
+
+        let (_file, tmp_path, path) = super::open_temp_file(filename, false, true).unwrap();
+
+        assert_eq!(tmp_path, dir.join("demo.cast.tmp"));
+        assert_eq!(path, target);
+        assert!(!target.exists());
+
+        fs::write(&target, "{}").unwrap();
+
+        let result = super::open_temp_file(filename, false, true);
+
+        assert!(result.unwrap_err().to_string().contains("already exists"));
+        assert!(super::open_temp_file(filename, true, true).is_ok());
+
+        // the link's target is what gets replaced
+        let link = link.to_str().unwrap();
+        let (_file, _tmp_path, path) = super::open_temp_file(link, true, true).unwrap();
+
+        assert_eq!(path, fs::canonicalize(&target).unwrap());
+        assert!(super::open_temp_file(link, true, false).is_err());
+    }
+
+    #[test]
+    fn output_command() {
+        use std::io::Write;
+
+        let dir = TempDir::new();
+        let path = dir.join("demo.cast");
+
+        let command = format!("tr a-z A-Z > {}", path.display());
+        let mut child = super::spawn_output_command(&command).unwrap();
+        child.take_stdin().write_all(b"hello").unwrap();
+
+        assert!(child.wait().unwrap().success());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "HELLO");
+
+        // gets waited for when dropped, e.g. on an error
+        let path = dir.join("dropped.cast");
+        let command = format!("sleep 0.1; tr a-z A-Z > {}", path.display());
+        let mut child = super::spawn_output_command(&command).unwrap();
+        child.take_stdin().write_all(b"hello").unwrap();
+        drop(child);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "HELLO");
+    }
+}
@@ -0,0 +1,928 @@
+use crate::convert;
+use crate::format::asciicast::{self, Event, EventCode};
+use crate::format::raw;
+use crate::notifier::Notifier;
+use crate::tty::RawMode;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::stat;
+use std::cell::Cell;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SPEED_STEP: f64 = 1.1;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const SPEED_PRESETS: [f64; 5] = [1.0, 1.5, 2.0, 3.0, 5.0];
+const CLEAR_SCREEN: &[u8] = b"\x1b[H\x1b[2J";
+
+pub trait Tty {
+    /// Waits for a key press for at most `timeout`, or indefinitely if `None`.
+    fn read_key(&mut self, timeout: Option<Duration>) -> io::Result<Option<Vec<u8>>>;
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Quit,
+    TogglePause,
+    Step,
+    NextMarker,
+    SpeedUp,
+    SlowDown,
+    SetSpeed(f64),
+}
+
+/// Playback position, printed on a line of its own.
+struct Progress {
+    sink: Box<dyn Write>,
+    duration: f64,
+}
+
+/// What happens between iterations of a looped playback.
+pub struct Loop {
+    pub delay: Duration,
+    /// Clear the screen before starting over.
+    pub clear: bool,
+}
+
+struct Timeline {
+    speed: f64,
+    time: f64,
+    instant: Instant,
+    paused: bool,
+}
+
+/// Replays recordings in the terminal. `Player::default()` plays at normal
+/// speed, once, honouring the recording's own idle time limit.
+pub struct Player {
+    pub idle_time_limit: Option<f64>,
+    pub speed: f64,
+    /// Rate at which a raw recording, which has no timing of its own, is
+    /// replayed, in bytes per second.
+    pub assume_rate: Option<f64>,
+    pub loop_: Option<Loop>,
+    pub pause_on_markers: bool,
+    pub quiet: bool,
+    pub max_events: Option<usize>,
+    /// Part of the recording to play, in seconds of playback time (i.e. with
+    /// idle time limited). Output before `start_at` is printed right away, so
+    /// that the screen looks the way it did at that point.
+    pub start_at: Option<f64>,
+    pub end_at: Option<f64>,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            idle_time_limit: None,
+            speed: 1.0,
+            assume_rate: None,
+            loop_: None,
+            pause_on_markers: false,
+            quiet: false,
+            max_events: None,
+            start_at: None,
+            end_at: None,
+        }
+    }
+}
+
+impl Player {
+    /// Plays `filename`, `-` meaning stdin.
+    pub fn play(self, filename: &str) -> anyhow::Result<()> {
+        let Player {
+            idle_time_limit,
+            speed,
+            assume_rate,
+            loop_,
+            pause_on_markers,
+            quiet,
+            max_events,
+            start_at,
+            end_at,
+        } = self;
+
+        let (mut tty, interactive): (Box<dyn Tty>, bool) = match DevTty::open() {
+            Ok(tty) => (Box::new(tty), true),
+            Err(_) => (Box::new(NullTty), false),
+        };
+
+        // without a tty there's no way to resume playback
+        let pause_on_markers = pause_on_markers && interactive;
+        let mut progress = (!quiet && progress_visible()).then(|| Progress::new(io::stderr()));
+        let mut speed = speed;
+        let input = Input::new(filename, loop_.is_some() || progress.is_some())?;
+        let backwards = Cell::new(false);
+
+        loop {
+            let mut reader = input.open()?;
+
+            let result = if is_asciicast(&mut reader)? {
+                let open = |reader| -> anyhow::Result<_> {
+                    let (header, events) = asciicast::open(reader)?;
+                    let events = asciicast::limit(events, max_events);
+                    let idle_time_limit = idle_time_limit.or(header.idle_time_limit.map(f64::from));
+                    let events = clamp_time(events, &backwards);
+                    let events = limit_idle_time(events, idle_time_limit);
+
+                    Ok(window(events, start_at, end_at))
+                };
+
+                // the file is read twice, so that playback can start right away
+                if let Some(progress) = &mut progress {
+                    progress.duration = duration(open(input.open()?)?);
+                }
+
+                play_events(
+                    open(reader)?,
+                    &mut *tty,
+                    speed,
+                    pause_on_markers,
+                    progress.as_mut(),
+                )?
+            } else {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+
+                let Some(rate) = assume_rate else {
+                    dump_raw(&mut io::stdout(), &data)?;
+
+                    Notifier::stderr(quiet).notice(
+                        "raw recording has no timing information, use --assume-rate to replay it",
+                    );
+
+                    break;
+                };
+
+                let open = || {
+                    let (_, events) = convert::read_raw(&data, rate);
+                    let events = asciicast::limit(events, max_events);
+                    let events = limit_idle_time(events, idle_time_limit);
+
+                    window(events, start_at, end_at)
+                };
+
+                if let Some(progress) = &mut progress {
+                    progress.duration = duration(open());
+                }
+
+                play_events(
+                    open(),
+                    &mut *tty,
+                    speed,
+                    pause_on_markers,
+                    progress.as_mut(),
+                )?
+            };
+
+            match result {
+                Some(s) => speed = s,
+                None => break,
+            }
+
+            let Some(loop_) = &loop_ else {
+                break;
+            };
+
+            if !wait(&mut *tty, loop_.delay)? {
+                break;
+            }
+
+            if loop_.clear {
+                let mut stdout = io::stdout();
+                stdout.write_all(CLEAR_SCREEN)?;
+                stdout.flush()?;
+            }
+        }
+
+        if let Some(progress) = &mut progress {
+            progress.clear()?;
+        }
+
+        if backwards.get() {
+            Notifier::stderr(quiet).warning(
+                "event timestamps go backwards in places, such events were played without delay",
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the recording gets read from, again for every iteration of a loop,
+/// and for the duration shown with progress. Stdin can only be read once, so
+/// in these cases it's kept in memory as a whole.
+enum Input<'a> {
+    File(&'a str),
+    Stdin,
+    Buffered(Vec<u8>),
+}
+
+impl<'a> Input<'a> {
+    /// `-` means stdin.
+    fn new(filename: &'a str, reread: bool) -> io::Result<Self> {
+        match filename {
+            "-" if reread => {
+                let mut data = Vec::new();
+                io::stdin().read_to_end(&mut data)?;
+
+                Ok(Input::Buffered(data))
+            }
+
+            "-" => Ok(Input::Stdin),
+            _ => Ok(Input::File(filename)),
+        }
+    }
+
+    fn open(&self) -> io::Result<Box<dyn BufRead + '_>> {
+        match self {
+            Input::File(filename) => Ok(Box::new(io::BufReader::new(fs::File::open(filename)?))),
+            Input::Stdin => Ok(Box::new(io::stdin().lock())),
+            Input::Buffered(data) => Ok(Box::new(&data[..])),
+        }
+    }
+}
+
+/// Progress goes to stderr, but only when it's a terminal other than the one
+/// the recording is played in, where it would mess up the replayed screen.
+fn progress_visible() -> bool {
+    if !io::stderr().is_terminal() {
+        return false;
+    }
+
+    if !io::stdout().is_terminal() {
+        return true;
+    }
+
+    match (
+        stat::fstat(libc::STDOUT_FILENO),
+        stat::fstat(libc::STDERR_FILENO),
+    ) {
+        (Ok(stdout), Ok(stderr)) => stdout.st_rdev != stderr.st_rdev,
+        _ => false,
+    }
+}
+
+/// Time of the last event shown during playback.
+fn duration<I>(events: I) -> f64
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    events
+        .map_while(Result::ok)
+        .filter(|e| matches!(e.code, EventCode::Output | EventCode::Marker))
+        .last()
+        .map_or(0.0, |e| e.time)
+}
+
+/// Raw recordings are plain terminal output, while asciicast always starts
+/// with a JSON header.
+fn is_asciicast<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    Ok(reader.fill_buf()?.starts_with(b"{"))
+}
+
+/// Writes raw output as is, minus the size sequence `raw::Writer` puts at the
+/// beginning, which would otherwise resize the terminal.
+fn dump_raw<W: Write>(sink: &mut W, data: &[u8]) -> io::Result<()> {
+    let len = raw::parse_size(data).map_or(0, |(_, len)| len);
+    sink.write_all(&data[len..])?;
+
+    sink.flush()
+}
+
+/// Plays events in real time, returning the speed in effect at the end, or
+/// `None` if the user quit.
+fn play_events<I>(
+    events: I,
+    tty: &mut dyn Tty,
+    speed: f64,
+    pause_on_markers: bool,
+    mut progress: Option<&mut Progress>,
+) -> anyhow::Result<Option<f64>>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    let mut stdout = io::stdout();
+    let mut events = events.filter(|e| {
+        e.as_ref().map_or(true, |e| {
+            matches!(e.code, EventCode::Output | EventCode::Marker)
+        })
+    });
+    let mut timeline = Timeline::new(speed, Instant::now());
+    let mut next = events.next().transpose()?;
+
+    while let Some(event) = &next {
+        // with progress shown, wake up regularly to update it
+        let mut early = false;
+
+        let key = if timeline.paused {
+            tty.read_key(None)?
+        } else {
+            let delay = timeline.delay(event.time, Instant::now());
+
+            if delay.is_zero() {
+                None
+            } else if progress.is_some() && delay > PROGRESS_INTERVAL {
+                early = true;
+                tty.read_key(Some(PROGRESS_INTERVAL))?
+            } else {
+                tty.read_key(Some(delay))?
+            }
+        };
+
+        if let Some(progress) = &mut progress {
+            progress.show(timeline.position(Instant::now()))?;
+        }
+
+        let Some(key) = key else {
+            if timeline.paused || early {
+                continue;
+            }
+
+            write_event(&mut stdout, event)?;
+
+            if pause_on_markers && event.code == EventCode::Marker {
+                timeline.seek(event.time, Instant::now());
+                timeline.paused = true;
+            }
+
+            next = events.next().transpose()?;
+            continue;
+        };
+
+        match parse_key(&key) {
+            Some(Action::Quit) => return Ok(None),
+
+            Some(Action::Step) if timeline.paused => {
+                write_event(&mut stdout, event)?;
+                timeline.seek(event.time, Instant::now());
+                next = events.next().transpose()?;
+            }
+
+            Some(Action::NextMarker) if timeline.paused => {
+                while let Some(event) = next.take() {
+                    write_event(&mut stdout, &event)?;
+                    timeline.seek(event.time, Instant::now());
+                    next = events.next().transpose()?;
+
+                    if event.code == EventCode::Marker {
+                        break;
+                    }
+                }
+            }
+
+            Some(action) => timeline.apply(action, Instant::now()),
+
+            None => (),
+        }
+    }
+
+    Ok(Some(timeline.speed))
+}
+
+/// Waits for `delay`, returning `false` if the user quit in the meantime.
+/// Other keys are ignored.
+fn wait(tty: &mut dyn Tty, delay: Duration) -> io::Result<bool> {
+    let deadline = Instant::now() + delay;
+
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+
+        if left.is_zero() {
+            return Ok(true);
+        }
+
+        if let Some(key) = tty.read_key(Some(left))? {
+            if parse_key(&key) == Some(Action::Quit) {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Moves events which are timed earlier than the ones before them (as in
+/// some hand-edited recordings) forward to the time of the previous event,
+/// setting `backwards` when that happens.
+fn clamp_time<'a, I>(
+    events: I,
+    backwards: &'a Cell<bool>,
+) -> impl Iterator<Item = anyhow::Result<Event>> + 'a
+where
+    I: Iterator<Item = anyhow::Result<Event>> + 'a,
+{
+    let mut prev_time = 0.0;
+
+    events.map(move |event| {
+        event.map(|mut event| {
+            if event.time < prev_time {
+                event.time = prev_time;
+                backwards.set(true);
+            }
+
+            prev_time = event.time;
+
+            event
+        })
+    })
+}
+
+/// Shifts event times so that no gap between consecutive events exceeds
+/// `limit` seconds. A limit of 0 removes idle time altogether, putting all
+/// events at time 0.
+pub fn limit_idle_time<I>(
+    events: I,
+    limit: Option<f64>,
+) -> impl Iterator<Item = anyhow::Result<Event>>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    // a negative limit would move events back in time, and NaN is no limit
+    let limit = match limit {
+        Some(limit) if limit.is_nan() => f64::INFINITY,
+        Some(limit) => limit.max(0.0),
+        None => f64::INFINITY,
+    };
+
+    let mut prev_time = 0.0;
+    let mut offset = 0.0;
+
+    events.map(move |event| {
+        event.map(|mut event| {
+            let gap = event.time - prev_time;
+            prev_time = event.time;
+
+            if gap > limit {
+                offset += gap - limit;
+            }
+
+            // events timed earlier than the previous ones can't go below 0
+            event.time = (event.time - offset).max(0.0);
+
+            event
+        })
+    })
+}
+
+/// Keeps the part of the timeline between `start` and `end`, moved to its
+/// beginning. Output from before `start` is kept too, at time 0, so that it
+/// gets printed right away, bringing the screen to the state it was in at
+/// `start`.
+fn window<I>(
+    events: I,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> impl Iterator<Item = anyhow::Result<Event>>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    let start = start.unwrap_or(0.0);
+    let end = end.unwrap_or(f64::INFINITY);
+
+    events
+        .take_while(move |e| e.as_ref().map_or(true, |e| e.time <= end))
+        .filter(move |e| {
+            e.as_ref()
+                .map_or(true, |e| e.time >= start || e.code == EventCode::Output)
+        })
+        .map(move |e| {
+            e.map(|mut e| {
+                e.time = (e.time - start).max(0.0);
+                e
+            })
+        })
+}
+
+fn write_event<W: Write>(sink: &mut W, event: &Event) -> io::Result<()> {
+    if event.code == EventCode::Output {
+        sink.write_all(event.data.as_bytes())?;
+        sink.flush()?;
+    }
+
+    Ok(())
+}
+
+fn parse_key(key: &[u8]) -> Option<Action> {
+    if key.contains(&0x03) {
+        return Some(Action::Quit);
+    }
+
+    match key {
+        b" " => Some(Action::TogglePause),
+        b"." => Some(Action::Step),
+        b"]" => Some(Action::NextMarker),
+        b"+" => Some(Action::SpeedUp),
+        b"-" => Some(Action::SlowDown),
+        b"0" => Some(Action::SetSpeed(1.0)),
+        [n @ b'1'..=b'5'] => Some(Action::SetSpeed(SPEED_PRESETS[(n - b'1') as usize])),
+        _ => None,
+    }
+}
+
+impl Progress {
+    fn new<W: Write + 'static>(sink: W) -> Self {
+        Self {
+            sink: Box::new(sink),
+            duration: 0.0,
+        }
+    }
+
+    fn show(&mut self, time: f64) -> io::Result<()> {
+        write!(
+            self.sink,
+            "\r{} / {}\x1b[K",
+            format_time(time.min(self.duration)),
+            format_time(self.duration)
+        )?;
+
+        self.sink.flush()
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.sink.write_all(b"\r\x1b[K")?;
+
+        self.sink.flush()
+    }
+}
+
+fn format_time(secs: f64) -> String {
+    let secs = secs as u64;
+
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+impl Timeline {
+    fn new(speed: f64, now: Instant) -> Self {
+        Self {
+            speed,
+            time: 0.0,
+            instant: now,
+            paused: false,
+        }
+    }
+
+    /// Current position on the recording's timeline.
+    fn position(&self, now: Instant) -> f64 {
+        if self.paused {
+            self.time
+        } else {
+            self.time + (now - self.instant).as_secs_f64() * self.speed
+        }
+    }
+
+    /// Wall clock time left until given point of the recording's timeline.
+    fn delay(&self, time: f64, now: Instant) -> Duration {
+        Duration::from_secs_f64(((time - self.position(now)) / self.speed).max(0.0))
+    }
+
+    fn seek(&mut self, time: f64, now: Instant) {
+        self.time = time;
+        self.instant = now;
+    }
+
+    fn apply(&mut self, action: Action, now: Instant) {
+        self.seek(self.position(now), now);
+
+        match action {
+            Action::TogglePause => self.paused = !self.paused,
+            Action::SpeedUp => self.speed *= SPEED_STEP,
+            Action::SlowDown => self.speed /= SPEED_STEP,
+            Action::SetSpeed(speed) => self.speed = speed,
+            _ => (),
+        }
+    }
+}
+
+struct DevTty(RawMode<fs::File>);
+
+impl DevTty {
+    fn open() -> io::Result<Self> {
+        let tty = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")?;
+
+        Ok(Self(RawMode::new(tty)?))
+    }
+}
+
+impl Tty for DevTty {
+    fn read_key(&mut self, timeout: Option<Duration>) -> io::Result<Option<Vec<u8>>> {
+        let timeout = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        let mut fds = [PollFd::new(&*self.0, PollFlags::POLLIN)];
+
+        if poll(&mut fds, timeout)? == 0 {
+            return Ok(None);
+        }
+
+        let mut buf = [0u8; 1024];
+        let n = (&*self.0).read(&mut buf)?;
+
+        Ok(Some(buf[..n].to_vec()))
+    }
+}
+
+struct NullTty;
+
+impl Tty for NullTty {
+    fn read_key(&mut self, timeout: Option<Duration>) -> io::Result<Option<Vec<u8>>> {
+        if let Some(timeout) = timeout {
+            thread::sleep(timeout);
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{duration, Action, NullTty, Progress, Timeline};
+    use crate::format::asciicast::{self, Event};
+    use std::cell::{Cell, RefCell};
+    use std::fs;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    struct SharedSink(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn events(times: &[f64]) -> impl Iterator<Item = anyhow::Result<Event>> {
+        times
+            .iter()
+            .map(|&time| Ok(Event::output(time, b"")))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn parse_key() {
+        assert_eq!(super::parse_key(b"\x03"), Some(Action::Quit));
+        assert_eq!(super::parse_key(b" "), Some(Action::TogglePause));
+        assert_eq!(super::parse_key(b"0"), Some(Action::SetSpeed(1.0)));
+        assert_eq!(super::parse_key(b"2"), Some(Action::SetSpeed(1.5)));
+        assert_eq!(super::parse_key(b"5"), Some(Action::SetSpeed(5.0)));
+        assert_eq!(super::parse_key(b"6"), None);
+        assert_eq!(super::parse_key(b"\x1b[A"), None);
+    }
+
+    #[test]
+    fn speed_preset() {
+        let now = Instant::now();
+        let mut timeline = Timeline::new(1.0, now);
+
+        assert_eq!(timeline.delay(6.0, now), Duration::from_secs(6));
+
+        timeline.apply(super::parse_key(b"4").unwrap(), now);
+
+        assert_eq!(timeline.delay(6.0, now), Duration::from_secs(2));
+
+        let later = now + Duration::from_secs(1);
+        timeline.apply(super::parse_key(b"0").unwrap(), later);
+
+        assert_eq!(timeline.position(later), 3.0);
+        assert_eq!(timeline.delay(6.0, later), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn speed() {
+        let now = Instant::now();
+
+        assert_eq!(
+            Timeline::new(2.0, now).delay(3.0, now),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(
+            Timeline::new(0.5, now).delay(3.0, now),
+            Duration::from_secs(6)
+        );
+
+        // idle time is limited before speed is applied
+        let events = super::limit_idle_time(events(&[10.0]), Some(2.0));
+        let time = events.map(|e| e.unwrap().time).next().unwrap();
+
+        assert_eq!(
+            Timeline::new(2.0, now).delay(time, now),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn pause() {
+        let now = Instant::now();
+        let mut timeline = Timeline::new(2.0, now);
+        let later = now + Duration::from_secs(1);
+
+        timeline.apply(Action::TogglePause, later);
+
+        assert_eq!(timeline.position(later + Duration::from_secs(10)), 2.0);
+    }
+
+    #[test]
+    fn limit_idle_time() {
+        let times = super::limit_idle_time(events(&[0.5, 1.0, 5.0, 5.5, 10.0]), Some(2.0))
+            .map(|e| e.unwrap().time)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, vec![0.5, 1.0, 3.0, 3.5, 5.5]);
+
+        let times = super::limit_idle_time(events(&[1.0, 5.0]), None)
+            .map(|e| e.unwrap().time)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, vec![1.0, 5.0]);
+
+        let times = super::limit_idle_time(events(&[0.5, 1.0, 5.0, 3.0]), Some(0.0))
+            .map(|e| e.unwrap().time)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, vec![0.0, 0.0, 0.0, 0.0]);
+
+        let times = super::limit_idle_time(events(&[1.0, 5.0]), Some(-1.0))
+            .chain(super::limit_idle_time(events(&[1.0, 5.0]), Some(f64::NAN)))
+            .map(|e| e.unwrap().time)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, vec![0.0, 0.0, 1.0, 5.0]);
+    }
+
+    #[test]
+    fn clamp_time() {
+        let backwards = Cell::new(false);
+        let times = super::clamp_time(events(&[1.0, 2.0, 3.0]), &backwards)
+            .map(|e| e.unwrap().time)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, vec![1.0, 2.0, 3.0]);
+        assert!(!backwards.get());
+
+        let cast = fs::read("tests/out-of-order.cast").unwrap();
+        let (_, events) = asciicast::open(&cast[..]).unwrap();
+        let events = super::clamp_time(events, &backwards);
+        let times = super::limit_idle_time(events, Some(2.0))
+            .map(|e| e.unwrap().time)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, vec![1.0, 3.0, 3.0, 3.5]);
+        assert!(backwards.get());
+    }
+
+    #[test]
+    fn window() {
+        let events = || {
+            vec![
+                Ok(Event::output(1.0, b"a")),
+                Ok(Event::marker(2.0, None)),
+                Ok(Event::output(3.0, b"b")),
+                Ok(Event::marker(4.0, None)),
+                Ok(Event::output(5.0, b"c")),
+                Ok(Event::output(7.0, b"d")),
+            ]
+            .into_iter()
+        };
+
+        let window = |start, end| {
+            super::window(events(), start, end)
+                .map(|e| e.map(|e| (e.time, e.code.to_string(), e.data)).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let event = |time, code: &str, data: &str| (time, code.to_owned(), data.to_owned());
+
+        // earlier output comes right away, earlier markers are skipped
+        assert_eq!(
+            window(Some(3.5), Some(5.0)),
+            vec![
+                event(0.0, "o", "a"),
+                event(0.0, "o", "b"),
+                event(0.5, "m", ""),
+                event(1.5, "o", "c"),
+            ]
+        );
+
+        assert_eq!(window(None, Some(2.5)).len(), 2);
+        assert_eq!(window(Some(6.0), None).last(), Some(&event(1.0, "o", "d")));
+        assert_eq!(window(None, None).len(), 6);
+    }
+
+    #[test]
+    fn is_asciicast() {
+        let mut cast = io::BufReader::new(fs::File::open("tests/ls/short.cast").unwrap());
+        let mut raw = io::BufReader::new(fs::File::open("tests/ls/output.raw").unwrap());
+
+        assert!(super::is_asciicast(&mut cast).unwrap());
+        assert!(!super::is_asciicast(&mut raw).unwrap());
+
+        // detection doesn't consume anything
+        assert!(asciicast::open(cast).is_ok());
+    }
+
+    #[test]
+    fn buffered_input() {
+        let data = fs::read("tests/ls/short.cast").unwrap();
+        let input = super::Input::Buffered(data);
+
+        for _ in 0..2 {
+            let mut reader = input.open().unwrap();
+
+            assert!(super::is_asciicast(&mut reader).unwrap());
+
+            let (_, events) = asciicast::open(reader).unwrap();
+
+            assert!(duration(events) > 0.0);
+        }
+    }
+
+    #[test]
+    fn dump_raw() {
+        let mut output = Vec::new();
+        super::dump_raw(&mut output, b"\x1b[8;24;80thello\r\n").unwrap();
+
+        assert_eq!(output, b"hello\r\n");
+    }
+
+    #[test]
+    fn progress() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut progress = Progress::new(SharedSink(output.clone()));
+        progress.duration = duration(events(&[0.5, 1.0]));
+
+        assert_eq!(progress.duration, 1.0);
+
+        let start = Instant::now();
+        let speed = super::play_events(
+            events(&[0.5, 1.0]),
+            &mut NullTty,
+            2.0,
+            false,
+            Some(&mut progress),
+        )
+        .unwrap();
+
+        assert_eq!(speed, Some(2.0));
+        assert!(start.elapsed() < Duration::from_millis(800));
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+
+        assert!(output.starts_with("\r0:00 / 0:01\x1b[K"));
+        assert!(output.ends_with("\r0:01 / 0:01\x1b[K"));
+    }
+
+    #[test]
+    fn wait() {
+        struct Keys(Vec<&'static [u8]>);
+
+        impl super::Tty for Keys {
+            fn read_key(&mut self, _timeout: Option<Duration>) -> io::Result<Option<Vec<u8>>> {
+                Ok(self.0.pop().map(Vec::from))
+            }
+        }
+
+        let start = Instant::now();
+
+        assert!(super::wait(&mut NullTty, Duration::from_millis(100)).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(super::wait(&mut NullTty, Duration::ZERO).unwrap());
+
+        let mut keys = Keys(vec![b"\x03", b" "]);
+
+        assert!(!super::wait(&mut keys, Duration::from_secs(10)).unwrap());
+        assert!(keys.0.is_empty());
+    }
+
+    #[test]
+    fn format_time() {
+        assert_eq!(super::format_time(0.0), "0:00");
+        assert_eq!(super::format_time(59.9), "0:59");
+        assert_eq!(super::format_time(754.0), "12:34");
+    }
+
+    #[test]
+    fn play_idle_time_limit() {
+        let limited = super::limit_idle_time(events(&[1.0, 2.0, 3.0, 4.0]), Some(0.05));
+        let start = Instant::now();
+
+        let speed = super::play_events(limited, &mut NullTty, 1.0, false, None).unwrap();
+
+        assert_eq!(speed, Some(1.0));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // no pauses at all
+        let no_pauses = super::limit_idle_time(events(&[1.0, 2.0, 3.0, 4.0]), Some(0.0));
+        let start = Instant::now();
+
+        super::play_events(no_pauses, &mut NullTty, 1.0, false, None).unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
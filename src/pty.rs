@@ -1,40 +1,141 @@
+use crate::hotkey::{Action, Hotkeys};
+use crate::tty::RawMode;
 use anyhow::bail;
 use mio::unix::SourceFd;
-use nix::{fcntl, libc, pty, sys::signal, sys::wait, unistd, unistd::ForkResult};
+use nix::{fcntl, libc, pty, sys::signal, sys::termios, sys::wait, unistd, unistd::ForkResult};
 use signal_hook::consts::signal::*;
 use signal_hook_mio::v0_8::Signals;
-use std::ffi::{CString, NulError};
+use std::env;
+use std::ffi::{CString, NulError, OsString};
 use std::fs;
 use std::io::{self, Read, Write};
-use std::ops::Deref;
-use std::os::fd::RawFd;
+use std::os::fd::{AsFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::io::{AsRawFd, FromRawFd};
-use termion::raw::IntoRawMode;
+use std::os::unix::net::UnixStream;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Set for the recorded command, so it knows it's being recorded.
+pub const REC_ENV_VAR: &str = "ASCIINEMA_REC";
+
+/// Shell running the recorded command, with the option making it take the
+/// command as an argument.
+pub const DEFAULT_EXEC_SHELL: &str = "/bin/sh -c";
 
 pub trait Recorder {
     fn start(&mut self, size: (u16, u16)) -> io::Result<()>;
     fn output(&mut self, data: &[u8]);
-    fn input(&mut self, data: &[u8]);
+    /// Called with all input, which gets passed on to the recorded program
+    /// either way, so it's up to the recorder whether to save it.
+    ///
+    /// `echo_suppressed` tells whether the terminal was reading input
+    /// without echoing it back, e.g. at a password prompt.
+    fn input(&mut self, data: &[u8], echo_suppressed: bool);
     fn resize(&mut self, size: (u16, u16));
+    /// Called when the marker hotkey gets pressed.
+    fn marker(&mut self, label: Option<&str>);
+
+    /// Called when the pause hotkey toggles recording. While paused, output
+    /// and input don't get to the recorder, resizes still do.
+    fn set_paused(&mut self, _paused: bool) {}
+
+    /// Tells whether the recording can't go on, e.g. for lack of disk space,
+    /// which ends it the same way as reaching the deadline.
+    fn failed(&self) -> bool {
+        false
+    }
+
+    /// Called whenever the copy loop wakes up, returns how long it may wait
+    /// for the next event before calling this again.
+    fn tick(&mut self) -> Option<Duration> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Exited(i32),
+    Signaled(i32),
+}
+
+/// How recording a command with `exec` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit {
+    pub status: ExitStatus,
+    /// Recording was stopped rather than ended by the command, i.e. by a stop
+    /// signal, the deadline, a failure of the recorder or the terminal going
+    /// away.
+    pub stopped: bool,
+}
+
+/// Size of the pty in one dimension, either fixed or following the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Auto,
+    Fixed(u16),
+}
+
+impl FromStr for Dimension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Dimension::Auto),
+
+            _ => match s.parse() {
+                Ok(0) | Err(_) => Err("expected a positive number or auto".to_owned()),
+                Ok(n) => Ok(Dimension::Fixed(n)),
+            },
+        }
+    }
+}
+
+impl ExitStatus {
+    /// Status the way shells report it, i.e. 128 + signal number for
+    /// processes killed by a signal.
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Exited(status) => *status,
+            ExitStatus::Signaled(signal) => 128 + signal,
+        }
+    }
 }
 
 pub fn exec<S: AsRef<str>, R: Recorder>(
     args: &[S],
     env: &[CString],
-    winsize_override: (Option<u16>, Option<u16>),
+    winsize_override: (Option<Dimension>, Option<Dimension>),
+    hotkeys: Option<Hotkeys>,
+    buffer_size: usize,
+    max_time: Option<Duration>,
     recorder: &mut R,
-) -> anyhow::Result<i32> {
-    let tty = open_tty()?;
-    let winsize = get_tty_size(tty.as_raw_fd(), winsize_override);
+) -> anyhow::Result<Exit> {
+    let tty = match open_tty() {
+        Ok(tty) => Some(tty),
+
+        // headless recording, e.g. in CI
+        Err(_) if is_fixed(winsize_override) => None,
+
+        Err(e) => bail!(
+            "can't open /dev/tty ({}), use --cols and/or --rows to record without a terminal",
+            e
+        ),
+    };
+
+    let winsize = get_tty_size(tty.as_ref().map(|t| t.as_raw_fd()), winsize_override);
     recorder.start((winsize.ws_col, winsize.ws_row))?;
     let result = unsafe { pty::forkpty(Some(&winsize), None) }?;
 
     match result.fork_result {
         ForkResult::Parent { child } => handle_parent(
-            result.master.as_raw_fd(),
+            result.master,
             tty,
             child,
             winsize_override,
+            hotkeys,
+            buffer_size,
+            max_time.map(|max_time| Instant::now() + max_time),
             recorder,
         ),
 
@@ -45,64 +146,415 @@ pub fn exec<S: AsRef<str>, R: Recorder>(
     }
 }
 
+/// Tells whether the terminal reports a zero size, in which case `exec`
+/// falls back to 80 columns or 24 rows unless they're overridden.
+pub fn tty_size_unknown() -> bool {
+    let Ok(tty) = open_tty() else {
+        return false;
+    };
+
+    let mut winsize: pty::Winsize = unsafe { std::mem::zeroed() };
+    unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut winsize) };
+
+    winsize.ws_col == 0 || winsize.ws_row == 0
+}
+
+/// Size `exec` would give the pty, or `None` if it would refuse to record
+/// for lack of a terminal.
+pub fn size(winsize_override: (Option<Dimension>, Option<Dimension>)) -> Option<(u16, u16)> {
+    let tty = open_tty().ok();
+
+    if tty.is_none() && !is_fixed(winsize_override) {
+        return None;
+    }
+
+    let winsize = get_tty_size(tty.as_ref().map(|t| t.as_raw_fd()), winsize_override);
+
+    Some((winsize.ws_col, winsize.ws_row))
+}
+
+/// Arguments for `exec` running `command`, defaulting to $SHELL, with
+/// `shell`, defaulting to `DEFAULT_EXEC_SHELL`. The shell is split on
+/// whitespace, and the command appended to it as a single argument.
+pub fn build_exec_args(command: Option<String>, shell: Option<&str>) -> Vec<String> {
+    let command = command
+        .or(env::var("SHELL").ok())
+        .unwrap_or("/bin/sh".to_owned());
+
+    shell
+        .unwrap_or(DEFAULT_EXEC_SHELL)
+        .split_whitespace()
+        .map(String::from)
+        .chain(std::iter::once(command))
+        .collect()
+}
+
+/// Environment for `exec`, the current one with REC_ENV_VAR added and TERM
+/// replaced with `term`, if given.
+pub fn build_exec_env(term: Option<&str>) -> Vec<CString> {
+    env::vars_os()
+        .filter(|(k, _v)| term.is_none() || k != "TERM")
+        .chain(term.map(|term| ("TERM".into(), term.into())))
+        .map(format_env_var)
+        .chain(std::iter::once(
+            CString::new(format!("{}=1", REC_ENV_VAR)).unwrap(),
+        ))
+        .collect()
+}
+
+fn format_env_var((key, value): (OsString, OsString)) -> CString {
+    let mut key_value = key.into_vec();
+    key_value.push(b'=');
+    key_value.extend(value.into_vec());
+
+    CString::new(key_value).unwrap()
+}
+
+/// Records output read from `fd` (e.g. a fifo, or a pty of another process)
+/// until it gets closed, displaying it on the tty if there's one. Nothing is
+/// forked, so there's no input and no resizing in this mode.
+pub fn attach<R: Recorder>(
+    fd: RawFd,
+    winsize_override: (Option<Dimension>, Option<Dimension>),
+    buffer_size: usize,
+    recorder: &mut R,
+) -> anyhow::Result<()> {
+    let mut tty = open_tty().ok();
+    let winsize = get_tty_size(tty.as_ref().map(|t| t.as_raw_fd()), winsize_override);
+    recorder.start((winsize.ws_col, winsize.ws_row))?;
+
+    // the descriptor is handed over to us, so it gets closed on return
+    let mut source = unsafe { fs::File::from_raw_fd(fd) };
+    let mut poll = mio::Poll::new()?;
+    let mut events = mio::Events::with_capacity(128);
+    let mut signals = Signals::new(ATTACH_SIGNAL_POLICY.iter().map(|(signal, _)| *signal))?;
+    let mut buf = vec![0u8; buffer_size];
+    let mut output: Vec<u8> = Vec::with_capacity(buffer_size);
+
+    set_non_blocking(&fd)?;
+
+    poll.registry()
+        .register(&mut SourceFd(&fd), MASTER, mio::Interest::READABLE)?;
+
+    poll.registry()
+        .register(&mut signals, SIGNAL, mio::Interest::READABLE)?;
+
+    loop {
+        if let Err(e) = poll.poll(&mut events, recorder.tick()) {
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            } else {
+                bail!(e);
+            }
+        }
+
+        if recorder.failed() {
+            return Ok(());
+        }
+
+        for event in events.iter() {
+            match event.token() {
+                MASTER => {
+                    output.clear();
+                    read_all(&mut source, &mut buf, &mut output)?;
+
+                    if !output.is_empty() {
+                        recorder.output(&output);
+
+                        if let Some(tty) = &mut tty {
+                            tty.write_all(&output)?;
+                        }
+                    }
+
+                    if event.is_read_closed() {
+                        return Ok(());
+                    }
+                }
+
+                SIGNAL => {
+                    for signal in signals.pending() {
+                        match signal_policy(ATTACH_SIGNAL_POLICY, signal) {
+                            Some(SignalPolicy::Stop) => return Ok(()),
+                            Some(SignalPolicy::Suspend) => crate::tty::suspend()?,
+                            _ => (),
+                        }
+                    }
+                }
+
+                _ => (),
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_parent<R: Recorder>(
-    master_fd: RawFd,
-    tty: fs::File,
+    master: OwnedFd,
+    tty: Option<fs::File>,
     child: unistd::Pid,
-    winsize_override: (Option<u16>, Option<u16>),
+    winsize_override: (Option<Dimension>, Option<Dimension>),
+    hotkeys: Option<Hotkeys>,
+    buffer_size: usize,
+    deadline: Option<Instant>,
     recorder: &mut R,
-) -> anyhow::Result<i32> {
-    let copy_result = copy(master_fd, tty, child, winsize_override, recorder);
-    let wait_result = wait::waitpid(child, None);
-    copy_result?;
+) -> anyhow::Result<Exit> {
+    let mut tty = tty.map(RawMode::new).transpose()?;
+
+    // window size changes come as bytes written to a socket by the signal
+    // handler
+    let (resizes, resize_notifier) = UnixStream::pair()?;
+    let resize_signal = signal_hook::low_level::pipe::register(SIGWINCH, resize_notifier)?;
+
+    let copy_result = copy(
+        fs::File::from(master),
+        tty.as_deref_mut(),
+        resizes,
+        child,
+        winsize_override,
+        hotkeys,
+        buffer_size,
+        deadline,
+        recorder,
+    );
+
+    signal_hook::low_level::unregister(resize_signal);
+
+    // the terminal is back to normal while waiting for the child
+    drop(tty);
+    let wait_result = retry_interrupted(|| wait::waitpid(child, None));
+    let stopped = copy_result?;
+
+    let status = match wait_result? {
+        wait::WaitStatus::Exited(_pid, status) => ExitStatus::Exited(status),
+        wait::WaitStatus::Signaled(_pid, signal, ..) => ExitStatus::Signaled(signal as i32),
+        _ => ExitStatus::Exited(1),
+    };
+
+    Ok(Exit { status, stopped })
+}
+
+/// Errors of system calls, which may tell that the call got interrupted by a
+/// signal before it did anything.
+pub(crate) trait Interruptible {
+    fn interrupted(&self) -> bool;
+}
+
+impl Interruptible for nix::errno::Errno {
+    fn interrupted(&self) -> bool {
+        *self == nix::errno::Errno::EINTR
+    }
+}
+
+impl Interruptible for io::Error {
+    fn interrupted(&self) -> bool {
+        self.kind() == io::ErrorKind::Interrupted
+    }
+}
 
-    match wait_result {
-        Ok(wait::WaitStatus::Exited(_pid, status)) => Ok(status),
-        Ok(wait::WaitStatus::Signaled(_pid, signal, ..)) => Ok(128 + signal as i32),
-        Ok(_) => Ok(1),
-        Err(e) => Err(anyhow::anyhow!(e)),
+/// Calls `f` again for as long as it gets interrupted by a signal.
+pub(crate) fn retry_interrupted<T, E: Interruptible>(
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    loop {
+        match f() {
+            Err(e) if e.interrupted() => continue,
+            result => return result,
+        }
     }
 }
 
 const MASTER: mio::Token = mio::Token(0);
 const TTY: mio::Token = mio::Token(1);
 const SIGNAL: mio::Token = mio::Token(2);
-const BUF_SIZE: usize = 128 * 1024;
+const RESIZE: mio::Token = mio::Token(3);
+/// What's done with a signal asciinema gets while recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SignalPolicy {
+    /// Passed on to the recorded command.
+    Forward,
+    Ignore,
+    /// Ends the recording, terminating the recorded command.
+    Stop,
+    /// Stops asciinema along with the recorded command, until both get
+    /// continued.
+    Suspend,
+}
+
+/// SIGWINCH isn't here, as it's never passed on as such, it resizes the pty
+/// (see `copy`).
+/// Ctrl-Z doesn't raise SIGTSTP in raw mode, it goes to the recorded command
+/// as input, so SIGTSTP only comes from elsewhere, e.g. `kill -TSTP`.
+const SIGNAL_POLICY: &[(i32, SignalPolicy)] = &[
+    (SIGINT, SignalPolicy::Ignore),
+    (SIGTERM, SignalPolicy::Stop),
+    (SIGQUIT, SignalPolicy::Stop),
+    (SIGHUP, SignalPolicy::Stop),
+    (SIGUSR1, SignalPolicy::Forward),
+    (SIGUSR2, SignalPolicy::Forward),
+    (SIGTSTP, SignalPolicy::Suspend),
+];
+
+/// Policy of `attach`, where there's no child to forward signals to, and the
+/// terminal isn't in raw mode, so <ctrl-c> raises SIGINT.
+const ATTACH_SIGNAL_POLICY: &[(i32, SignalPolicy)] = &[
+    (SIGINT, SignalPolicy::Stop),
+    (SIGTERM, SignalPolicy::Stop),
+    (SIGQUIT, SignalPolicy::Stop),
+    (SIGHUP, SignalPolicy::Stop),
+    (SIGTSTP, SignalPolicy::Suspend),
+];
+
+fn signal_policy(policies: &[(i32, SignalPolicy)], signal: i32) -> Option<SignalPolicy> {
+    policies
+        .iter()
+        .find(|(s, _)| *s == signal)
+        .map(|(_, policy)| *policy)
+}
 
-fn copy<R: Recorder>(
-    master_fd: RawFd,
-    tty: fs::File,
+/// Size of the buffer data is read from the pty and the tty with, by default.
+pub const DEFAULT_BUF_SIZE: usize = 128 * 1024;
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
+/// Longest a burst of window size changes may hold the change back.
+const RESIZE_MAX_WAIT: Duration = Duration::from_millis(250);
+/// How long the pty may stay open after the child exited, for the rest of its
+/// output to arrive.
+const CHILD_EXIT_GRACE: Duration = Duration::from_millis(100);
+/// Shown on the terminal, without getting recorded, when the pause hotkey
+/// gets pressed.
+const PAUSED_NOTICE: &[u8] = b"\r\nasciinema: recording paused\r\n";
+const RESUMED_NOTICE: &[u8] = b"\r\nasciinema: recording resumed\r\n";
+
+/// Collapses a burst of window size changes, e.g. while the window's edge
+/// is being dragged, into one, applied once the changes stop for a moment.
+/// A burst going on for long still gets applied every `RESIZE_MAX_WAIT`, so
+/// the size follows the dragging instead of only catching up after it ends.
+#[derive(Default)]
+struct Debounce {
+    due: Option<Instant>,
+    burst_start: Option<Instant>,
+}
+
+impl Debounce {
+    fn trigger(&mut self, now: Instant) {
+        let burst_start = *self.burst_start.get_or_insert(now);
+        self.due = Some((now + RESIZE_DEBOUNCE).min(burst_start + RESIZE_MAX_WAIT));
+    }
+
+    /// How long until the pending change is due.
+    fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.due.map(|due| due.saturating_duration_since(now))
+    }
+
+    /// Tells whether the pending change is due, clearing it then.
+    fn fire(&mut self, now: Instant) -> bool {
+        let due = self.due.is_some_and(|due| due <= now);
+
+        if due {
+            self.due = None;
+            self.burst_start = None;
+        }
+
+        due
+    }
+}
+
+/// Without a tty the output is only recorded, and there's no input. Returns
+/// whether recording got stopped, see `Exit::stopped`.
+///
+/// Once `deadline` passes the child gets terminated, the same way as when
+/// asciinema itself gets SIGTERM.
+///
+/// `master` and `tty` are normally the pty master and /dev/tty (already in
+/// raw mode), but anything pollable will do, e.g. sockets in tests. The same
+/// goes for `resizes`, which gets readable when the terminal gets resized.
+///
+/// The master is owned here and gets closed once, when `master` is dropped
+/// on return. `master_fd` merely borrows it, for polling and resizing, so it
+/// must not outlive `master`.
+#[allow(clippy::too_many_arguments)]
+fn copy<M, T, S, R>(
+    mut master: M,
+    mut tty: Option<T>,
+    mut resizes: S,
     child: unistd::Pid,
-    winsize_override: (Option<u16>, Option<u16>),
+    winsize_override: (Option<Dimension>, Option<Dimension>),
+    mut hotkeys: Option<Hotkeys>,
+    buffer_size: usize,
+    deadline: Option<Instant>,
     recorder: &mut R,
-) -> anyhow::Result<()> {
-    let mut master = unsafe { fs::File::from_raw_fd(master_fd) };
+) -> anyhow::Result<bool>
+where
+    M: Read + Write + AsFd,
+    T: Read + Write + AsFd,
+    S: Read + AsFd,
+    R: Recorder,
+{
+    let master_fd = master.as_fd().as_raw_fd();
     let mut poll = mio::Poll::new()?;
     let mut events = mio::Events::with_capacity(128);
     let mut master_source = SourceFd(&master_fd);
-    let mut tty = tty.into_raw_mode()?;
-    let tty_fd = tty.as_raw_fd();
-    let mut tty_source = SourceFd(&tty_fd);
-    let mut signals = Signals::new([SIGWINCH, SIGINT, SIGTERM, SIGQUIT, SIGHUP])?;
-    let mut buf = [0u8; BUF_SIZE];
-    let mut input: Vec<u8> = Vec::with_capacity(BUF_SIZE);
-    let mut output: Vec<u8> = Vec::with_capacity(BUF_SIZE);
+    let tty_fd = tty.as_ref().map(|tty| tty.as_fd().as_raw_fd());
+    let mut tty_source = tty_fd.as_ref().map(SourceFd);
+    let resizes_fd = resizes.as_fd().as_raw_fd();
+    let mut resizes_source = SourceFd(&resizes_fd);
+    let mut signals = Signals::new(
+        SIGNAL_POLICY
+            .iter()
+            .map(|(signal, _)| *signal)
+            .chain([SIGCHLD]),
+    )?;
+    let mut buf = vec![0u8; buffer_size];
+    let mut input: Vec<u8> = Vec::with_capacity(buffer_size);
+    let mut output: Vec<u8> = Vec::with_capacity(buffer_size);
     let mut flush = false;
+    let mut resize = Debounce::default();
+    let mut reading = true;
+    let mut paused = false;
+    // when to stop waiting for the pty to get closed after the child exited
+    let mut child_exit: Option<Instant> = None;
+    let mut stopped = false;
 
     set_non_blocking(&master_fd)?;
-    set_non_blocking(&tty_fd)?;
 
     poll.registry()
         .register(&mut master_source, MASTER, mio::Interest::READABLE)?;
 
-    poll.registry()
-        .register(&mut tty_source, TTY, mio::Interest::READABLE)?;
+    if let (Some(tty_fd), Some(tty_source)) = (&tty_fd, &mut tty_source) {
+        set_non_blocking(tty_fd)?;
+
+        poll.registry()
+            .register(tty_source, TTY, mio::Interest::READABLE)?;
+    }
 
     poll.registry()
         .register(&mut signals, SIGNAL, mio::Interest::READABLE)?;
 
+    set_non_blocking(&resizes_fd)?;
+
+    poll.registry()
+        .register(&mut resizes_source, RESIZE, mio::Interest::READABLE)?;
+
     loop {
-        if let Err(e) = poll.poll(&mut events, None) {
+        debug_assert!(
+            fcntl::fcntl(master_fd, fcntl::F_GETFD).is_ok(),
+            "pty master closed while still in use"
+        );
+
+        let now = Instant::now();
+
+        let timeout = [
+            recorder.tick(),
+            resize.timeout(now),
+            deadline
+                .filter(|_| reading)
+                .map(|deadline| deadline.saturating_duration_since(now)),
+            child_exit.map(|child_exit| child_exit.saturating_duration_since(now)),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        if let Err(e) = poll.poll(&mut events, timeout) {
             if e.kind() == io::ErrorKind::Interrupted {
                 continue;
             } else {
@@ -110,6 +562,26 @@ fn copy<R: Recorder>(
             }
         }
 
+        // output that's come so far still gets recorded, and to the terminal,
+        // the same way as on hangup
+        if reading
+            && (deadline.is_some_and(|deadline| deadline <= Instant::now()) || recorder.failed())
+        {
+            unsafe { libc::kill(child.as_raw(), SIGTERM) };
+            stopped = true;
+        }
+
+        // something else (e.g. a background job) may keep the pty open after
+        // the child exited, which would keep the recording going forever
+        let mut hangup =
+            stopped || child_exit.is_some_and(|child_exit| child_exit <= Instant::now());
+
+        if resize.fire(Instant::now()) {
+            let winsize = get_tty_size(tty_fd, winsize_override);
+            set_pty_size(master_fd, &winsize);
+            recorder.resize((winsize.ws_col, winsize.ws_row));
+        }
+
         for event in events.iter() {
             match event.token() {
                 MASTER => {
@@ -118,13 +590,19 @@ fn copy<R: Recorder>(
                         let read = read_all(&mut master, &mut buf, &mut output)?;
 
                         if read > 0 {
-                            recorder.output(&output[offset..]);
+                            if !paused {
+                                recorder.output(&output[offset..]);
+                            }
 
-                            poll.registry().reregister(
-                                &mut tty_source,
-                                TTY,
-                                mio::Interest::READABLE | mio::Interest::WRITABLE,
-                            )?;
+                            if let Some(tty_source) = &mut tty_source {
+                                poll.registry().reregister(
+                                    tty_source,
+                                    TTY,
+                                    mio::Interest::READABLE | mio::Interest::WRITABLE,
+                                )?;
+                            } else {
+                                output.clear();
+                            }
                         }
                     }
 
@@ -141,26 +619,24 @@ fn copy<R: Recorder>(
                     }
 
                     if event.is_read_closed() {
-                        poll.registry().deregister(&mut master_source)?;
-
-                        if !output.is_empty() {
-                            flush = true;
-                        } else {
-                            return Ok(());
-                        }
+                        hangup = true;
                     }
                 }
 
                 TTY => {
+                    // only registered when there's a tty
+                    let tty = tty.as_mut().unwrap();
+                    let tty_source = tty_source.as_mut().unwrap();
+
                     if event.is_writable() {
-                        let left = write_all(&mut tty, &mut output)?;
+                        let left = write_all(tty, &mut output)?;
 
                         if left == 0 {
                             if flush {
-                                return Ok(());
+                                return Ok(stopped);
                             } else {
                                 poll.registry().reregister(
-                                    &mut tty_source,
+                                    tty_source,
                                     TTY,
                                     mio::Interest::READABLE,
                                 )?;
@@ -170,10 +646,44 @@ fn copy<R: Recorder>(
 
                     if event.is_readable() {
                         let offset = input.len();
-                        let read = read_all(&mut tty.deref(), &mut buf, &mut input)?;
+                        read_all(tty, &mut buf, &mut input)?;
+                        let was_paused = paused;
+
+                        if let Some(hotkeys) = &mut hotkeys {
+                            let (data, actions) = hotkeys.process(&input[offset..]);
+                            input.truncate(offset);
+                            input.extend_from_slice(&data);
+
+                            for action in actions {
+                                match action {
+                                    Action::Marker(label) => recorder.marker(label.as_deref()),
+
+                                    Action::Pause => {
+                                        paused = !paused;
+                                        recorder.set_paused(paused);
+
+                                        output.extend_from_slice(match paused {
+                                            true => PAUSED_NOTICE,
+                                            false => RESUMED_NOTICE,
+                                        });
+
+                                        poll.registry().reregister(
+                                            tty_source,
+                                            TTY,
+                                            mio::Interest::READABLE | mio::Interest::WRITABLE,
+                                        )?;
+                                    }
+                                }
+                            }
+                        }
 
-                        if read > 0 {
-                            recorder.input(&input[offset..]);
+                        // the program is gone once the pty stops being read
+                        if input.len() > offset && reading {
+                            // input read along with the pause hotkey may be
+                            // what's not to be recorded
+                            if !was_paused && !paused {
+                                recorder.input(&input[offset..], is_echo_suppressed(&master));
+                            }
 
                             poll.registry().reregister(
                                 &mut master_source,
@@ -184,25 +694,65 @@ fn copy<R: Recorder>(
                     }
 
                     if event.is_read_closed() {
-                        poll.registry().deregister(&mut tty_source).unwrap();
-                        return Ok(());
+                        poll.registry().deregister(tty_source).unwrap();
+                        return Ok(true);
+                    }
+                }
+
+                RESIZE => {
+                    let mut notifications = Vec::new();
+                    read_all(&mut resizes, &mut buf, &mut notifications)?;
+
+                    if tty_fd.is_some() {
+                        resize.trigger(Instant::now());
+                    }
+
+                    // nothing's going to come anymore
+                    if event.is_read_closed() {
+                        poll.registry().deregister(&mut resizes_source)?;
                     }
                 }
 
                 SIGNAL => {
                     for signal in signals.pending() {
-                        match signal {
-                            SIGWINCH => {
-                                let winsize = get_tty_size(tty_fd, winsize_override);
-                                set_pty_size(master_fd, &winsize);
-                                recorder.resize((winsize.ws_col, winsize.ws_row));
+                        // output the child wrote right before exiting may not
+                        // be readable yet, hence the grace period
+                        if signal == SIGCHLD {
+                            if reading && child_exit.is_none() && !is_running(child) {
+                                child_exit = Some(Instant::now() + CHILD_EXIT_GRACE);
                             }
 
-                            SIGINT => (),
+                            continue;
+                        }
 
-                            SIGTERM | SIGQUIT | SIGHUP => {
+                        match signal_policy(SIGNAL_POLICY, signal) {
+                            Some(SignalPolicy::Stop) => {
                                 unsafe { libc::kill(child.as_raw(), SIGTERM) };
-                                return Ok(());
+                                return Ok(true);
+                            }
+
+                            // a reaped child's pid could belong to another process by now
+                            Some(SignalPolicy::Forward) if is_running(child) => {
+                                unsafe { libc::kill(child.as_raw(), signal) };
+                            }
+
+                            Some(SignalPolicy::Suspend) => {
+                                let group = foreground_group(master_fd, child);
+
+                                if is_running(child) {
+                                    unsafe { libc::kill(-group.as_raw(), SIGSTOP) };
+                                }
+
+                                crate::tty::suspend()?;
+
+                                if is_running(child) {
+                                    unsafe { libc::kill(-group.as_raw(), SIGCONT) };
+                                }
+
+                                // the terminal may have been resized meanwhile
+                                if tty_fd.is_some() {
+                                    resize.trigger(Instant::now());
+                                }
                             }
 
                             _ => (),
@@ -213,6 +763,35 @@ fn copy<R: Recorder>(
                 _ => (),
             }
         }
+
+        if hangup && reading {
+            reading = false;
+            child_exit = None;
+
+            // hangup may be reported without readiness, make sure whatever is
+            // left in the buffer gets recorded too
+            let offset = output.len();
+
+            if read_all(&mut master, &mut buf, &mut output)? > 0 && !paused {
+                recorder.output(&output[offset..]);
+            }
+
+            poll.registry().deregister(&mut master_source)?;
+
+            match &mut tty_source {
+                Some(tty_source) if !output.is_empty() => {
+                    flush = true;
+
+                    poll.registry().reregister(
+                        tty_source,
+                        TTY,
+                        mio::Interest::READABLE | mio::Interest::WRITABLE,
+                    )?;
+                }
+
+                _ => return Ok(stopped),
+            }
+        }
     }
 }
 
@@ -236,7 +815,10 @@ fn open_tty() -> io::Result<fs::File> {
         .open("/dev/tty")
 }
 
-fn get_tty_size(tty_fd: i32, winsize_override: (Option<u16>, Option<u16>)) -> pty::Winsize {
+fn get_tty_size(
+    tty_fd: Option<RawFd>,
+    winsize_override: (Option<Dimension>, Option<Dimension>),
+) -> pty::Winsize {
     let mut winsize = pty::Winsize {
         ws_row: 24,
         ws_col: 80,
@@ -244,23 +826,71 @@ fn get_tty_size(tty_fd: i32, winsize_override: (Option<u16>, Option<u16>)) -> pt
         ws_ypixel: 0,
     };
 
-    unsafe { libc::ioctl(tty_fd, libc::TIOCGWINSZ, &mut winsize) };
+    if let Some(tty_fd) = tty_fd {
+        unsafe { libc::ioctl(tty_fd, libc::TIOCGWINSZ, &mut winsize) };
+    }
+
+    // some terminals (e.g. ptys of CI runners) report a zero size, which
+    // programs can't work with
+    if winsize.ws_col == 0 {
+        winsize.ws_col = 80;
+    }
 
-    if let Some(cols) = winsize_override.0 {
+    if winsize.ws_row == 0 {
+        winsize.ws_row = 24;
+    }
+
+    // a zero-sized pty is invalid, so such an override is no override
+    if let Some(Dimension::Fixed(cols @ 1..)) = winsize_override.0 {
         winsize.ws_col = cols;
     }
 
-    if let Some(rows) = winsize_override.1 {
+    if let Some(Dimension::Fixed(rows @ 1..)) = winsize_override.1 {
         winsize.ws_row = rows;
     }
 
     winsize
 }
 
+/// Tells whether at least one dimension is fixed, which is what allows
+/// recording without a terminal.
+fn is_fixed(winsize_override: (Option<Dimension>, Option<Dimension>)) -> bool {
+    let fixed = |dimension| matches!(dimension, Some(Dimension::Fixed(_)));
+
+    fixed(winsize_override.0) || fixed(winsize_override.1)
+}
+
 fn set_pty_size(pty_fd: i32, winsize: &pty::Winsize) {
     unsafe { libc::ioctl(pty_fd, libc::TIOCSWINSZ, winsize) };
 }
 
+/// Checks whether the child is still running, without reaping it if it's not.
+fn is_running(child: unistd::Pid) -> bool {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    let result =
+        unsafe { libc::waitid(libc::P_PID, child.as_raw() as libc::id_t, &mut info, flags) };
+
+    result == 0 && unsafe { info.si_pid() } == 0
+}
+
+/// Process group of the job in the foreground of the pty, the one Ctrl-Z
+/// would stop, defaulting to the child's own group.
+fn foreground_group(master_fd: RawFd, child: unistd::Pid) -> unistd::Pid {
+    unistd::tcgetpgrp(master_fd).unwrap_or(child)
+}
+
+/// Password prompts read a whole line with echo turned off. Line editors
+/// (readline etc.) also turn echo off, but they read input in non-canonical
+/// mode and echo it themselves, so those don't count.
+fn is_echo_suppressed<Fd: AsFd>(fd: Fd) -> bool {
+    use termios::LocalFlags;
+
+    termios::tcgetattr(fd).is_ok_and(|t| {
+        t.local_flags.contains(LocalFlags::ICANON) && !t.local_flags.contains(LocalFlags::ECHO)
+    })
+}
+
 fn set_non_blocking(fd: &RawFd) -> Result<(), io::Error> {
     use fcntl::{fcntl, FcntlArg::*, OFlag};
 
@@ -277,28 +907,39 @@ fn read_all<R: Read>(source: &mut R, buf: &mut [u8], out: &mut Vec<u8>) -> io::R
 
     loop {
         match source.read(buf) {
-            Ok(0) => (),
+            Ok(0) => break,
 
             Ok(n) => {
                 out.extend_from_slice(&buf[0..n]);
                 read += n;
             }
 
-            Err(_) => {
+            Err(e) if is_transient(&e) => {
                 break;
             }
+
+            Err(e) => return Err(e),
         }
     }
 
     Ok(read)
 }
 
+fn is_transient(e: &io::Error) -> bool {
+    // EIO is what reading the pty master returns once the slave side is closed
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    ) || e.raw_os_error() == Some(libc::EIO)
+}
+
 fn write_all<W: Write>(sink: &mut W, data: &mut Vec<u8>) -> io::Result<usize> {
     let mut buf: &[u8] = data.as_ref();
 
     loop {
         match sink.write(buf) {
-            Ok(0) => (),
+            // sink accepts no more for now, leave the rest for the next poll
+            Ok(0) => break,
 
             Ok(n) => {
                 buf = &buf[n..];
@@ -329,10 +970,22 @@ fn write_all<W: Write>(sink: &mut W, data: &mut Vec<u8>) -> io::Result<usize> {
 
 #[cfg(test)]
 mod tests {
+    use super::Dimension::{Auto, Fixed};
+    use super::{Hotkeys, PAUSED_NOTICE, RESUMED_NOTICE};
+    use nix::unistd;
+    use std::io::{self, Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
     #[derive(Default)]
     struct TestRecorder {
         size: Option<(u16, u16)>,
         output: Vec<Vec<u8>>,
+        input: Vec<Vec<u8>>,
+        resizes: Vec<(u16, u16)>,
+        paused: Vec<bool>,
+        failed: bool,
     }
 
     impl super::Recorder for TestRecorder {
@@ -345,8 +998,23 @@ mod tests {
             self.output.push(data.into());
         }
 
-        fn input(&mut self, _data: &[u8]) {}
-        fn resize(&mut self, _size: (u16, u16)) {}
+        fn input(&mut self, data: &[u8], _echo_suppressed: bool) {
+            self.input.push(data.into());
+        }
+
+        fn resize(&mut self, size: (u16, u16)) {
+            self.resizes.push(size);
+        }
+
+        fn marker(&mut self, _label: Option<&str>) {}
+
+        fn set_paused(&mut self, paused: bool) {
+            self.paused.push(paused);
+        }
+
+        fn failed(&self) -> bool {
+            self.failed
+        }
     }
 
     impl TestRecorder {
@@ -358,6 +1026,86 @@ mod tests {
         }
     }
 
+    struct TestReader(Vec<io::Result<Vec<u8>>>);
+
+    impl io::Read for TestReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.remove(0) {
+                Ok(data) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    Ok(data.len())
+                }
+
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    #[test]
+    fn read_all() {
+        use super::libc;
+
+        let mut buf = [0u8; 16];
+
+        for e in [
+            io::Error::from(io::ErrorKind::WouldBlock),
+            io::Error::from(io::ErrorKind::Interrupted),
+            io::Error::from_raw_os_error(libc::EIO),
+        ] {
+            let mut reader = TestReader(vec![Ok(b"foo".to_vec()), Ok(b"bar".to_vec()), Err(e)]);
+            let mut out = Vec::new();
+
+            assert_eq!(super::read_all(&mut reader, &mut buf, &mut out).unwrap(), 6);
+            assert_eq!(out, b"foobar");
+        }
+
+        let mut reader = TestReader(vec![
+            Ok(b"foo".to_vec()),
+            Err(io::Error::from_raw_os_error(libc::EBADF)),
+        ]);
+
+        let mut out = Vec::new();
+        let result = super::read_all(&mut reader, &mut buf, &mut out);
+
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EBADF));
+    }
+
+    struct TestWriter(Vec<usize>);
+
+    impl io::Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(self.0.pop().unwrap_or(0).min(buf.len()))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_all() {
+        let mut data = b"foobarbaz".to_vec();
+        let mut sink = TestWriter(vec![3, 3]);
+
+        assert_eq!(super::write_all(&mut sink, &mut data).unwrap(), 3);
+        assert_eq!(data, b"baz");
+
+        let mut sink = TestWriter(vec![5]);
+
+        assert_eq!(super::write_all(&mut sink, &mut data).unwrap(), 0);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn read_all_eof() {
+        let mut reader = TestReader(vec![Ok(b"foo".to_vec()), Ok(Vec::new())]);
+        let mut buf = [0u8; 16];
+        let mut out = Vec::new();
+
+        assert_eq!(super::read_all(&mut reader, &mut buf, &mut out).unwrap(), 3);
+        assert_eq!(out, b"foo");
+    }
+
     #[test]
     fn exec() {
         let mut recorder = TestRecorder::default();
@@ -371,10 +1119,536 @@ time.sleep(0.01);
 sys.stdout.write('bar');
 "#;
 
-        let result = super::exec(&["python3", "-c", code], &[], (None, None), &mut recorder);
+        let result = super::exec(
+            &["python3", "-c", code],
+            &[],
+            (None, None),
+            None,
+            super::DEFAULT_BUF_SIZE,
+            None,
+            &mut recorder,
+        );
 
         assert!(result.is_ok());
         assert!(recorder.size.is_some());
         assert_eq!(recorder.output(), vec!["foo", "bar"]);
     }
+
+    #[test]
+    fn exec_status() {
+        let mut recorder = TestRecorder::default();
+        let result = super::exec(
+            &["/bin/sh", "-c", "exit 3"],
+            &[],
+            (None, None),
+            None,
+            super::DEFAULT_BUF_SIZE,
+            None,
+            &mut recorder,
+        );
+
+        assert_eq!(result.unwrap().status, super::ExitStatus::Exited(3));
+
+        let mut recorder = TestRecorder::default();
+        let result = super::exec(
+            &["/bin/sh", "-c", "kill -TERM $$"],
+            &[],
+            (None, None),
+            None,
+            super::DEFAULT_BUF_SIZE,
+            None,
+            &mut recorder,
+        );
+
+        let status = result.unwrap().status;
+
+        assert_eq!(status, super::ExitStatus::Signaled(15));
+        assert_eq!(status.code(), 128 + 15);
+    }
+
+    #[test]
+    fn exec_trailing_output() {
+        for _ in 0..10 {
+            let mut recorder = TestRecorder::default();
+            let args = ["/bin/sh", "-c", "printf 'foo\\nbar\\nbaz'; exit 0"];
+            let result = super::exec(
+                &args,
+                &[],
+                (None, None),
+                None,
+                super::DEFAULT_BUF_SIZE,
+                None,
+                &mut recorder,
+            );
+
+            assert_eq!(
+                result.unwrap(),
+                super::Exit {
+                    status: super::ExitStatus::Exited(0),
+                    stopped: false
+                }
+            );
+            assert_eq!(recorder.output().concat(), "foo\r\nbar\r\nbaz");
+        }
+    }
+
+    #[test]
+    fn copy() {
+        let (master, mut pty) = UnixStream::pair().unwrap();
+        let (mut tty, mut terminal) = UnixStream::pair().unwrap();
+
+        // a child which is running, as far as the loop can tell
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let pid = unistd::Pid::from_raw(child.id() as i32);
+
+        let (resizes, mut resize) = UnixStream::pair().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut recorder = TestRecorder::default();
+
+            super::copy(
+                master,
+                Some(&mut tty),
+                resizes,
+                pid,
+                (Some(Fixed(100)), Some(Fixed(30))),
+                None,
+                16,
+                None,
+                &mut recorder,
+            )
+            .unwrap();
+
+            recorder
+        });
+
+        // input gets to the program
+        terminal.write_all(b"ls\r").unwrap();
+        let mut buf = [0u8; 3];
+        pty.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, b"ls\r");
+
+        // output gets to the terminal
+        pty.write_all(b"foo\r\n").unwrap();
+        let mut buf = [0u8; 5];
+        terminal.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, b"foo\r\n");
+
+        // the way the SIGWINCH handler lets the loop know
+        resize.write_all(&[0]).unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        // output written right before closing still makes it to the terminal
+        pty.write_all(b"bye").unwrap();
+        drop(pty);
+        let mut rest = Vec::new();
+        terminal.read_to_end(&mut rest).unwrap();
+        let recorder = handle.join().unwrap();
+
+        assert_eq!(rest, b"bye");
+        assert_eq!(recorder.input, vec![b"ls\r".to_vec()]);
+        assert_eq!(recorder.output().concat(), "foo\r\nbye");
+        assert_eq!(recorder.resizes, vec![(100, 30)]);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn copy_paused() {
+        let (master, mut pty) = UnixStream::pair().unwrap();
+        let (mut tty, mut terminal) = UnixStream::pair().unwrap();
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let pid = unistd::Pid::from_raw(child.id() as i32);
+
+        let (resizes, _resize) = UnixStream::pair().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut recorder = TestRecorder::default();
+
+            super::copy(
+                master,
+                Some(&mut tty),
+                resizes,
+                pid,
+                (Some(Fixed(100)), Some(Fixed(30))),
+                Some(Hotkeys::new(None, Some(0x10))),
+                16,
+                None,
+                &mut recorder,
+            )
+            .unwrap();
+
+            recorder
+        });
+
+        let read = |stream: &mut UnixStream, len| {
+            let mut buf = vec![0u8; len];
+            stream.read_exact(&mut buf).unwrap();
+            buf
+        };
+
+        terminal.write_all(b"ls\r").unwrap();
+        assert_eq!(read(&mut pty, 3), b"ls\r");
+
+        // what happens while paused still gets through, unrecorded
+        terminal.write_all(b"\x10").unwrap();
+        assert_eq!(read(&mut terminal, PAUSED_NOTICE.len()), PAUSED_NOTICE);
+        pty.write_all(b"password: ").unwrap();
+        assert_eq!(read(&mut terminal, 10), b"password: ");
+        terminal.write_all(b"secret\r").unwrap();
+        assert_eq!(read(&mut pty, 7), b"secret\r");
+
+        terminal.write_all(b"\x10").unwrap();
+        assert_eq!(read(&mut terminal, RESUMED_NOTICE.len()), RESUMED_NOTICE);
+        pty.write_all(b"done").unwrap();
+        assert_eq!(read(&mut terminal, 4), b"done");
+
+        drop(pty);
+        let recorder = handle.join().unwrap();
+
+        assert_eq!(recorder.input, vec![b"ls\r".to_vec()]);
+        assert_eq!(recorder.output().concat(), "done");
+        assert_eq!(recorder.paused, vec![true, false]);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn copy_deadline() {
+        let (master, mut pty) = UnixStream::pair().unwrap();
+        let (mut tty, mut terminal) = UnixStream::pair().unwrap();
+        let (resizes, _resize) = UnixStream::pair().unwrap();
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let pid = unistd::Pid::from_raw(child.id() as i32);
+
+        // output pending when the time's up still gets through
+        pty.write_all(b"last words").unwrap();
+        let mut recorder = TestRecorder::default();
+
+        let stopped = super::copy(
+            master,
+            Some(&mut tty),
+            resizes,
+            pid,
+            (Some(Fixed(100)), Some(Fixed(30))),
+            None,
+            16,
+            Some(Instant::now()),
+            &mut recorder,
+        )
+        .unwrap();
+
+        assert!(stopped);
+        assert_eq!(recorder.output().concat(), "last words");
+
+        drop(tty);
+        let mut rest = Vec::new();
+        terminal.read_to_end(&mut rest).unwrap();
+
+        assert_eq!(rest, b"last words");
+
+        // terminated, as the deadline has passed
+        assert!(!child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn exec_background_job() {
+        let mut recorder = TestRecorder::default();
+        // the job keeps the pty open after the shell exits
+        let args = ["/bin/sh", "-c", "sleep 10 & printf done"];
+        let start = Instant::now();
+
+        let result = super::exec(
+            &args,
+            &[],
+            (Some(Fixed(80)), Some(Fixed(24))),
+            None,
+            super::DEFAULT_BUF_SIZE,
+            None,
+            &mut recorder,
+        );
+
+        assert_eq!(result.unwrap().status, super::ExitStatus::Exited(0));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(recorder.output().concat(), "done");
+    }
+
+    #[test]
+    fn exec_failed() {
+        let mut recorder = TestRecorder {
+            failed: true,
+            ..Default::default()
+        };
+        let args = ["/bin/sh", "-c", "printf foo; sleep 10"];
+        let start = Instant::now();
+
+        let result = super::exec(
+            &args,
+            &[],
+            (Some(Fixed(80)), Some(Fixed(24))),
+            None,
+            super::DEFAULT_BUF_SIZE,
+            None,
+            &mut recorder,
+        );
+
+        let exit = result.unwrap();
+
+        // SIGTERM, or SIGHUP if the pty got closed first
+        assert!(matches!(exit.status, super::ExitStatus::Signaled(_)));
+        assert!(exit.stopped);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn exec_max_time() {
+        let mut recorder = TestRecorder::default();
+        let args = ["/bin/sh", "-c", "printf foo; sleep 10; printf bar"];
+        let start = Instant::now();
+
+        let result = super::exec(
+            &args,
+            &[],
+            (Some(Fixed(80)), Some(Fixed(24))),
+            None,
+            super::DEFAULT_BUF_SIZE,
+            Some(Duration::from_millis(200)),
+            &mut recorder,
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            super::Exit {
+                status: super::ExitStatus::Signaled(15),
+                stopped: true
+            }
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(recorder.output().concat(), "foo");
+    }
+
+    #[test]
+    fn exec_buffer_size() {
+        let mut recorder = TestRecorder::default();
+        let args = ["/bin/sh", "-c", "printf '%01000d' 0"];
+        let result = super::exec(
+            &args,
+            &[],
+            (Some(Fixed(80)), Some(Fixed(24))),
+            None,
+            16,
+            None,
+            &mut recorder,
+        );
+
+        assert_eq!(result.unwrap().status, super::ExitStatus::Exited(0));
+        assert_eq!(recorder.output().concat(), "0".repeat(1000));
+    }
+
+    #[test]
+    fn get_tty_size() {
+        let winsize = super::get_tty_size(None, (Some(Fixed(0)), Some(Fixed(30))));
+
+        assert_eq!((winsize.ws_col, winsize.ws_row), (80, 30));
+
+        let winsize = super::get_tty_size(None, (Some(Fixed(100)), Some(Fixed(0))));
+
+        assert_eq!((winsize.ws_col, winsize.ws_row), (100, 24));
+
+        let winsize = super::get_tty_size(None, (Some(Fixed(100)), Some(Auto)));
+
+        assert_eq!((winsize.ws_col, winsize.ws_row), (100, 24));
+    }
+
+    #[test]
+    fn build_exec_args() {
+        assert_eq!(
+            super::build_exec_args(Some("echo $((1 + 1))".to_owned()), None),
+            vec!["/bin/sh", "-c", "echo $((1 + 1))"]
+        );
+
+        assert_eq!(
+            super::build_exec_args(
+                Some("[[ -n x ]]".to_owned()),
+                Some(" /usr/bin/env  bash -c")
+            ),
+            vec!["/usr/bin/env", "bash", "-c", "[[ -n x ]]"]
+        );
+    }
+
+    #[test]
+    fn get_tty_size_zero() {
+        use std::os::unix::io::AsRawFd;
+
+        let pty = nix::pty::openpty(None, None).unwrap();
+        let winsize = super::get_tty_size(Some(pty.slave.as_raw_fd()), (None, None));
+
+        assert_eq!((winsize.ws_col, winsize.ws_row), (80, 24));
+
+        let winsize = super::get_tty_size(Some(pty.slave.as_raw_fd()), (None, Some(Fixed(10))));
+
+        assert_eq!((winsize.ws_col, winsize.ws_row), (80, 10));
+    }
+
+    #[test]
+    fn parse_dimension() {
+        assert_eq!("auto".parse(), Ok(Auto));
+        assert_eq!("100".parse(), Ok(Fixed(100)));
+        assert!("0".parse::<super::Dimension>().is_err());
+        assert!("-1".parse::<super::Dimension>().is_err());
+        assert!("Auto".parse::<super::Dimension>().is_err());
+
+        assert!(super::is_fixed((Some(Auto), Some(Fixed(24)))));
+        assert!(!super::is_fixed((Some(Auto), None)));
+    }
+
+    #[test]
+    fn debounce() {
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        let mut resize = super::Debounce::default();
+
+        assert_eq!(resize.timeout(now), None);
+        assert!(!resize.fire(now));
+
+        resize.trigger(now);
+        resize.trigger(now + ms(10));
+        resize.trigger(now + ms(20));
+
+        // each change puts it off
+        assert_eq!(resize.timeout(now + ms(20)), Some(ms(50)));
+        assert!(!resize.fire(now + ms(50)));
+        assert!(resize.fire(now + ms(70)));
+        assert!(!resize.fire(now + ms(80)));
+        assert_eq!(resize.timeout(now + ms(80)), None);
+
+        resize.trigger(now + ms(100));
+
+        assert_eq!(resize.timeout(now + ms(200)), Some(Duration::ZERO));
+        assert!(resize.fire(now + ms(200)));
+
+        // but not for longer than the max wait since the burst started
+        for i in 0..7 {
+            resize.trigger(now + ms(300 + i * 40));
+        }
+
+        assert_eq!(resize.timeout(now + ms(540)), Some(ms(10)));
+        assert!(resize.fire(now + ms(550)));
+        assert_eq!(resize.timeout(now + ms(550)), None);
+    }
+
+    #[test]
+    fn retry_interrupted() {
+        use nix::errno::Errno;
+
+        let mut results = vec![Ok(42), Err(Errno::EINTR), Err(Errno::EINTR)];
+        let result = super::retry_interrupted(|| results.pop().unwrap());
+
+        assert_eq!(result, Ok(42));
+        assert!(results.is_empty());
+
+        let result: nix::Result<()> = super::retry_interrupted(|| Err(Errno::ECHILD));
+
+        assert_eq!(result, Err(Errno::ECHILD));
+    }
+
+    #[test]
+    fn signal_policy() {
+        use super::SignalPolicy;
+        use signal_hook::consts::signal::*;
+
+        use super::{signal_policy, ATTACH_SIGNAL_POLICY, SIGNAL_POLICY};
+
+        assert_eq!(
+            signal_policy(SIGNAL_POLICY, SIGINT),
+            Some(SignalPolicy::Ignore)
+        );
+        assert_eq!(
+            signal_policy(SIGNAL_POLICY, SIGTERM),
+            Some(SignalPolicy::Stop)
+        );
+        assert_eq!(
+            signal_policy(SIGNAL_POLICY, SIGUSR1),
+            Some(SignalPolicy::Forward)
+        );
+        assert_eq!(
+            signal_policy(SIGNAL_POLICY, SIGTSTP),
+            Some(SignalPolicy::Suspend)
+        );
+        assert_eq!(signal_policy(SIGNAL_POLICY, SIGWINCH), None);
+
+        // <ctrl-c> ends attached recording
+        assert_eq!(
+            signal_policy(ATTACH_SIGNAL_POLICY, SIGINT),
+            Some(SignalPolicy::Stop)
+        );
+        assert_eq!(signal_policy(ATTACH_SIGNAL_POLICY, SIGUSR1), None);
+        assert_eq!(
+            signal_policy(ATTACH_SIGNAL_POLICY, SIGTSTP),
+            Some(SignalPolicy::Suspend)
+        );
+    }
+
+    #[test]
+    fn is_running() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let pid = nix::unistd::Pid::from_raw(child.id() as i32);
+
+        assert!(super::is_running(pid));
+
+        child.kill().unwrap();
+
+        while super::is_running(pid) {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        // not reaped by the check
+        assert!(child.try_wait().unwrap().is_some());
+    }
+
+    #[test]
+    fn attach() {
+        use std::io::Write;
+        use std::os::fd::FromRawFd;
+
+        let mut recorder = TestRecorder::default();
+        let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+
+        let writer = std::thread::spawn(move || {
+            let mut pipe = unsafe { std::fs::File::from_raw_fd(write_fd) };
+            pipe.write_all(b"foo").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            pipe.write_all(b"bar").unwrap();
+        });
+
+        let result = super::attach(
+            read_fd,
+            (Some(Fixed(100)), Some(Fixed(30))),
+            super::DEFAULT_BUF_SIZE,
+            &mut recorder,
+        );
+        writer.join().unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(recorder.size, Some((100, 30)));
+        assert_eq!(recorder.output().concat(), "foobar");
+    }
 }
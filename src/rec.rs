@@ -0,0 +1,704 @@
+use crate::cli::RecordArgs;
+use crate::environ::{capture_env, env_patterns, DEFAULT_ENV};
+use crate::format::{self, asciicast, raw};
+use crate::output::{check_appendable, open_output_file, open_temp_file, spawn_output_command};
+use crate::{hotkey, locale, meta, notifier, pty, recorder, socket};
+use anyhow::{bail, Context, Result};
+use nix::libc;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::time::{Duration, Instant};
+
+const NORMALIZED_TERM: &str = "xterm-256color";
+
+/// Runs `rec`, returning the exit status to exit with.
+pub fn run(args: RecordArgs) -> Result<u8> {
+    let RecordArgs {
+        filename,
+        output_command,
+        stdin,
+        no_echo_input,
+        input_names,
+        input_only,
+        append,
+        raw,
+        no_raw: _,
+        cls,
+        overwrite,
+        no_follow_symlinks,
+        tmp,
+        command,
+        exec_shell,
+        keep_going,
+        mark_commands,
+        fd,
+        env,
+        env_file,
+        no_redact,
+        record_cwd,
+        title,
+        theme,
+        idle_time_limit,
+        cols,
+        rows,
+        normalize_term,
+        quiet,
+        force,
+        batch_time,
+        fix_alt_screen,
+        max_time,
+        buffer_size,
+        constant_rate,
+        json_summary,
+        record_exit,
+        write_meta,
+        stream,
+        socket,
+        dry_run,
+        no_nested,
+        marker_key,
+        pause_key,
+    } = args;
+
+    let mut notifier = notifier::Notifier::stderr(quiet);
+
+    if let Err(e) = locale::check_utf8_locale() {
+        if !force {
+            return Err(e.into());
+        }
+
+        notifier.warning(e);
+    }
+
+    if is_nested(env::var_os(pty::REC_ENV_VAR)) {
+        if no_nested {
+            bail!("already recording, {} is set", pty::REC_ENV_VAR);
+        }
+
+        notifier.warning("already recording, this recording is nested in another one");
+    }
+
+    let marker_byte = marker_key.as_deref().map(hotkey::parse).transpose()?;
+    let pause_byte = pause_key.as_deref().map(hotkey::parse).transpose()?;
+
+    if marker_byte.is_some() && marker_byte == pause_byte {
+        bail!("the marker and pause hotkeys can't be the same key");
+    }
+    let term = normalize_term.then_some(NORMALIZED_TERM);
+
+    let metadata = recorder::Metadata {
+        idle_time_limit,
+        command: (!command.is_empty()).then(|| command.join("; ")),
+        // there's no command to default the title to when attached
+        title: if fd.is_some() {
+            title.or(Some(String::new()))
+        } else {
+            title
+        },
+        env: capture_env(
+            env::vars(),
+            &env_patterns(env.as_deref().unwrap_or(DEFAULT_ENV), env_file.as_deref())?,
+            !no_redact,
+            term,
+        ),
+        // opt-in, as paths may tell more than one would like to share
+        cwd: if record_cwd {
+            Some(env::current_dir()?.to_string_lossy().into_owned())
+        } else {
+            None
+        },
+        theme,
+        original_term: term.and(env::var("TERM").ok()),
+    };
+
+    let target = match (&filename, &output_command) {
+        (_, Some(command)) => format!("`{}`", command),
+        (Some(filename), None) => filename.clone(),
+        (None, None) => unreachable!("either is required"),
+    };
+
+    if dry_run {
+        let exec = match fd {
+            Some(fd) => vec![format!("output of fd {}", fd)],
+            None if command.is_empty() => vec![format!(
+                "{:?}",
+                pty::build_exec_args(None, exec_shell.as_deref())
+            )],
+
+            None => command
+                .into_iter()
+                .map(|command| {
+                    format!(
+                        "{:?}",
+                        pty::build_exec_args(Some(command), exec_shell.as_deref())
+                    )
+                })
+                .collect(),
+        };
+
+        let output = match (raw, input_only) {
+            (true, true) => "raw input",
+            (true, false) => "raw",
+            (false, _) => "asciicast",
+        };
+
+        let output = format!(
+            "{} ({}{})",
+            target,
+            output,
+            if append { ", append" } else { "" }
+        );
+        print_plan(
+            &mut io::stdout(),
+            &exec,
+            &metadata.env,
+            &output,
+            pty::size((cols, rows)),
+        )?;
+
+        return Ok(0);
+    }
+
+    // temporary file and the path it's to be moved to when done
+    let mut temp_file = None;
+
+    let (sink, append, mut output_child): (Box<dyn io::Write + Send>, _, _) =
+        match (&filename, &output_command) {
+            (_, Some(command)) => {
+                // waited for on every way out of here, once it's dropped
+                let mut child = spawn_output_command(command)?;
+                let stdin = child.take_stdin();
+
+                (Box::new(stdin), false, Some(child))
+            }
+
+            (Some(filename), None) if tmp => {
+                let (file, tmp_path, path) =
+                    open_temp_file(filename, overwrite, !no_follow_symlinks)?;
+                temp_file = Some((tmp_path, path));
+
+                (Box::new(file), false, None)
+            }
+
+            (Some(filename), None) => {
+                let (file, append) =
+                    open_output_file(filename, append, overwrite, !no_follow_symlinks)?;
+
+                if append && !raw {
+                    check_appendable(filename)?;
+                }
+
+                (Box::new(file), append, None)
+            }
+
+            (None, None) => unreachable!("either is required"),
+        };
+
+    let time_offset = match &filename {
+        Some(filename) if append && !raw => match asciicast::get_duration(filename) {
+            Ok(duration) => duration,
+            Err(e) if e.is::<asciicast::UntimedError>() => 0.0,
+            Err(e) => return Err(e),
+        },
+
+        _ => 0.0,
+    };
+
+    let mut writer: Box<dyn format::Writer + Send> = if raw && input_only {
+        Box::new(raw::Writer::input_only(sink))
+    } else if raw {
+        Box::new(raw::Writer::new(sink))
+    } else {
+        Box::new(asciicast::Writer::new(sink, time_offset))
+    };
+
+    // the session itself is shown on /dev/tty, so stdout is free for this
+    if stream {
+        if io::stdout().is_terminal() {
+            notifier.warning("stdout is a terminal, streamed events will show up in it");
+        }
+
+        let stdout = asciicast::Writer::new(io::stdout(), 0.0);
+        writer = Box::new(format::Tee(writer, stdout));
+    }
+
+    if let Some(path) = &socket {
+        let server =
+            socket::Server::bind(path).with_context(|| format!("can't create socket {}", path))?;
+
+        notifier.notice(format_args!("serving the recording live at {}", path));
+        writer = Box::new(format::Tee(writer, server));
+    }
+
+    let mut recorder = recorder::Recorder::new(
+        writer,
+        recorder::Settings {
+            append,
+            record_output: !input_only,
+            record_input: stdin || input_only,
+            record_unechoed_input: !no_echo_input,
+            record_key_names: input_names,
+            clear_screen: cls,
+            batch_time: batch_time as f64 / 1000.0,
+            frame_rate: constant_rate,
+        },
+        metadata,
+        Box::new(recorder::SystemClock),
+    );
+
+    if let Some(filename) = filename.as_ref().filter(|_| append && !raw) {
+        // the recording may not be asciicast, then there's no size to go by
+        if let Ok(size) = asciicast::get_size(filename) {
+            recorder.set_previous_size(size);
+        }
+    }
+
+    notifier.notice(format_args!("recording to {}", target));
+
+    let size_fixed = matches!(
+        (cols, rows),
+        (
+            Some(pty::Dimension::Fixed(_)),
+            Some(pty::Dimension::Fixed(_))
+        )
+    );
+
+    if !size_fixed && pty::tty_size_unknown() {
+        notifier.warning(
+            "the terminal reports zero size, recording at 80x24 unless given --cols and --rows",
+        );
+    }
+
+    let exit_status = if let Some(fd) = fd {
+        notifier.notice(format_args!(
+            "recording output of fd {} until it's closed",
+            fd
+        ));
+        pty::attach(fd, (cols, rows), buffer_size, &mut recorder)?;
+
+        pty::ExitStatus::Exited(0)
+    } else {
+        let exec_env = pty::build_exec_env(term);
+        notifier.notice("press <ctrl-d> or type \"exit\" when you're done");
+
+        if let Some(key) = &marker_key {
+            notifier.notice(format_args!(
+                "press {} to add a marker, type its label (or not) and press <enter>",
+                key
+            ));
+        }
+
+        if let Some(key) = &pause_key {
+            notifier.notice(format_args!(
+                "press {} to pause recording, and again to resume it",
+                key
+            ));
+        }
+
+        // no --command means the shell
+        let commands = match command.len() {
+            0 => vec![None],
+            _ => command.into_iter().map(Some).collect(),
+        };
+
+        let deadline = max_time.map(|max_time| Instant::now() + Duration::from_secs_f64(max_time));
+
+        record_commands(
+            commands,
+            deadline,
+            keep_going,
+            mark_commands,
+            &mut recorder,
+            |command, max_time, recorder| {
+                pty::exec(
+                    &pty::build_exec_args(command, exec_shell.as_deref()),
+                    &exec_env,
+                    (cols, rows),
+                    marker_byte
+                        .or(pause_byte)
+                        .map(|_| hotkey::Hotkeys::new(marker_byte, pause_byte)),
+                    buffer_size,
+                    max_time,
+                    recorder,
+                )
+            },
+        )?
+    };
+
+    if recorder.in_alt_screen() {
+        if fix_alt_screen {
+            recorder.leave_alt_screen();
+        } else {
+            notifier.warning("recording ends in the alternate screen, --fix-alt-screen switches back to the primary one");
+        }
+    }
+
+    if !raw && recorder.output_looks_binary() {
+        notifier.warning("much of the output looks like binary data, which takes a lot of space escaped in asciicast files, consider recording it with --raw");
+    }
+
+    if record_exit {
+        recorder.record_exit(exit_status);
+    }
+
+    recorder.flush();
+
+    // with output left out there's no telling whether anything got printed
+    if !input_only && recorder.metrics().output_events == 0 {
+        notifier.warning("the recorded command printed nothing, the recording is empty");
+    }
+
+    // written only once the recording is in place, see below
+    let meta = write_meta.then(|| {
+        meta::Meta::new(
+            recorder.metrics(),
+            recorder.env().keys(),
+            recorder.original_term(),
+            exit_status.code(),
+        )
+    });
+
+    let metrics = recorder.metrics().clone();
+    let duration = time_offset + metrics.duration;
+
+    // closes the output command's stdin, letting it finish
+    recorder.finish().map_err(|e| {
+        if e.raw_os_error() == Some(libc::ENOSPC) {
+            // with --tmp it's not moved in place, so it's left where it is
+            let saved_to = match &temp_file {
+                Some((tmp_path, _)) => tmp_path.display().to_string(),
+                None => target.clone(),
+            };
+
+            anyhow::anyhow!(
+                "disk full, recording to {} stopped early (it's saved up to that point in {})",
+                target,
+                saved_to
+            )
+        } else {
+            anyhow::Error::new(e).context(format!("can't save the recording to {}", target))
+        }
+    })?;
+
+    if let Some((tmp_path, path)) = &temp_file {
+        fs::rename(tmp_path, path)
+            .with_context(|| format!("can't move the recording to {}", path.display()))?;
+    }
+
+    if let Some((filename, meta)) = filename.as_ref().zip(meta) {
+        meta.write(meta::path_for(filename))?;
+    }
+
+    if let Some(child) = &mut output_child {
+        let status = child.wait()?;
+
+        if !status.success() {
+            bail!("output command {} failed ({})", target, status);
+        }
+    }
+
+    if json_summary {
+        let events = metrics.events();
+
+        eprintln!(
+            "{}",
+            serde_json::json!({ "path": filename, "duration": duration, "events": events })
+        );
+    } else {
+        notifier.notice(format_args!(
+            "recording saved to {} ({:.1}s)",
+            target, duration
+        ));
+    }
+
+    Ok(exit_status.code() as u8)
+}
+
+/// Records `commands` one after another with `exec`, which is given the time
+/// left until `deadline`. The recorder keeps the timeline going from one
+/// command to the next.
+///
+/// Recording ends at the first failing command, unless `keep_going`, and
+/// whenever it gets stopped, e.g. by SIGTERM. The returned status is the one
+/// of the first failure.
+fn record_commands<R, F>(
+    commands: Vec<Option<String>>,
+    deadline: Option<Instant>,
+    keep_going: bool,
+    mark_commands: bool,
+    recorder: &mut R,
+    mut exec: F,
+) -> Result<pty::ExitStatus>
+where
+    R: pty::Recorder,
+    F: FnMut(Option<String>, Option<Duration>, &mut R) -> Result<pty::Exit>,
+{
+    let mut first_failure = None;
+
+    for (i, command) in commands.into_iter().enumerate() {
+        let max_time = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        if max_time.is_some_and(|max_time| max_time.is_zero()) {
+            break;
+        }
+
+        if i > 0 && mark_commands {
+            recorder.marker(command.as_deref());
+        }
+
+        let exit = exec(command, max_time, recorder)?;
+
+        if exit.status.code() != 0 {
+            first_failure = first_failure.or(Some(exit.status));
+        }
+
+        if exit.stopped || (first_failure.is_some() && !keep_going) {
+            break;
+        }
+    }
+
+    Ok(first_failure.unwrap_or(pty::ExitStatus::Exited(0)))
+}
+
+/// Describes a recording for `rec --dry-run`.
+fn print_plan<W: io::Write>(
+    sink: &mut W,
+    exec: &[String],
+    env: &HashMap<String, String>,
+    output: &str,
+    size: Option<(u16, u16)>,
+) -> io::Result<()> {
+    for exec in exec {
+        writeln!(sink, "command: {}", exec)?;
+    }
+
+    let mut env = env.iter().collect::<Vec<_>>();
+    env.sort();
+
+    for (key, value) in env {
+        writeln!(sink, "env: {}={}", key, value)?;
+    }
+
+    writeln!(sink, "output: {}", output)?;
+
+    match size {
+        Some((cols, rows)) => writeln!(sink, "size: {}x{}", cols, rows),
+        None => writeln!(sink, "size: unknown, there's no terminal"),
+    }
+}
+
+fn is_nested(rec_env_var: Option<OsString>) -> bool {
+    rec_env_var.is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::asciicast;
+    use crate::pty::{self, Dimension::Fixed, Exit, ExitStatus};
+    use crate::recorder::{Metadata, Recorder, Settings, SystemClock};
+    use crate::test_util::TempDir;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    /// Keeps the markers only, for checking them along with the commands run.
+    #[derive(Default)]
+    struct Markers(Vec<Option<String>>);
+
+    impl pty::Recorder for Markers {
+        fn start(&mut self, _size: (u16, u16)) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn output(&mut self, _data: &[u8]) {}
+
+        fn input(&mut self, _data: &[u8], _echo_suppressed: bool) {}
+
+        fn resize(&mut self, _size: (u16, u16)) {}
+
+        fn marker(&mut self, label: Option<&str>) {
+            self.0.push(label.map(String::from));
+        }
+    }
+
+    /// Runs `record_commands` with exits given for each command in advance,
+    /// returning the resulting status along with the commands run.
+    fn run(
+        exits: &[(&str, i32, bool)],
+        deadline: Option<Instant>,
+        keep_going: bool,
+    ) -> (ExitStatus, Vec<String>) {
+        let mut run = Vec::new();
+
+        let status = super::record_commands(
+            exits.iter().map(|(c, ..)| Some(c.to_string())).collect(),
+            deadline,
+            keep_going,
+            false,
+            &mut Markers::default(),
+            |command, _max_time, _recorder| {
+                let command = command.unwrap();
+                let (_, status, stopped) = exits.iter().find(|(c, ..)| *c == command).unwrap();
+                run.push(command);
+
+                Ok(Exit {
+                    status: ExitStatus::Exited(*status),
+                    stopped: *stopped,
+                })
+            },
+        )
+        .unwrap();
+
+        (status, run)
+    }
+
+    #[test]
+    fn record_commands() {
+        let dir = TempDir::new();
+        let path = dir.join("demo.cast");
+        let file = fs::File::create(&path).unwrap();
+
+        let mut recorder = Recorder::new(
+            Box::new(asciicast::Writer::new(file, 0.0)),
+            Settings::default(),
+            Metadata::default(),
+            Box::new(SystemClock),
+        );
+
+        let status = super::record_commands(
+            vec![
+                Some("printf one".to_owned()),
+                Some("printf two; exit 3".to_owned()),
+                Some("printf three".to_owned()),
+            ],
+            None,
+            false,
+            true,
+            &mut recorder,
+            |command, max_time, recorder| {
+                pty::exec(
+                    &pty::build_exec_args(command, None),
+                    &pty::build_exec_env(None),
+                    (Some(Fixed(100)), Some(Fixed(30))),
+                    None,
+                    pty::DEFAULT_BUF_SIZE,
+                    max_time,
+                    recorder,
+                )
+            },
+        )
+        .unwrap();
+
+        drop(recorder);
+
+        assert_eq!(status, ExitStatus::Exited(3));
+
+        // one timeline, the size stayed the same
+        let (_header, events) =
+            asciicast::open(io::BufReader::new(fs::File::open(&path).unwrap())).unwrap();
+        let events = events
+            .map(|e| e.map(|e| format!("{}:{}", e.code, e.label.unwrap_or(e.data))))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(events, ["o:one", "m:printf two; exit 3", "o:two"]);
+    }
+
+    #[test]
+    fn keep_going() {
+        let exits = [("a", 1, false), ("b", 2, false), ("c", 0, false)];
+
+        assert_eq!(
+            run(&exits, None, false),
+            (ExitStatus::Exited(1), vec!["a".to_owned()])
+        );
+
+        let (status, run) = run(&exits, None, true);
+
+        assert_eq!(status, ExitStatus::Exited(1));
+        assert_eq!(run, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn stopped() {
+        // e.g. by SIGTERM, the deadline or a full disk
+        let exits = [("a", 0, false), ("b", 143, true), ("c", 0, false)];
+
+        assert_eq!(run(&exits, None, false).1, ["a", "b"]);
+        assert_eq!(run(&exits, None, true).1, ["a", "b"]);
+
+        let exits = [("a", 0, true), ("b", 0, false)];
+
+        assert_eq!(
+            run(&exits, None, true),
+            (ExitStatus::Exited(0), vec!["a".to_owned()])
+        );
+
+        let past = Instant::now() - Duration::from_secs(1);
+
+        assert!(run(&exits, Some(past), true).1.is_empty());
+    }
+
+    #[test]
+    fn mark_commands() {
+        let mut markers = Markers::default();
+
+        super::record_commands(
+            vec![None, Some("vim".to_owned())],
+            None,
+            false,
+            true,
+            &mut markers,
+            |_command, _max_time, _recorder| {
+                Ok(Exit {
+                    status: ExitStatus::Exited(0),
+                    stopped: false,
+                })
+            },
+        )
+        .unwrap();
+
+        assert_eq!(markers.0, [Some("vim".to_owned())]);
+    }
+
+    #[test]
+    fn print_plan() {
+        let env = HashMap::from([
+            ("TERM".to_owned(), "xterm".to_owned()),
+            ("SHELL".to_owned(), "/bin/zsh".to_owned()),
+        ]);
+
+        let mut output = Vec::new();
+        super::print_plan(
+            &mut output,
+            &["[\"/bin/sh\", \"-c\", \"zsh\"]".to_owned()],
+            &env,
+            "demo.cast (asciicast)",
+            Some((80, 24)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "command: [\"/bin/sh\", \"-c\", \"zsh\"]\n\
+             env: SHELL=/bin/zsh\n\
+             env: TERM=xterm\n\
+             output: demo.cast (asciicast)\n\
+             size: 80x24\n"
+        );
+    }
+
+    #[test]
+    fn is_nested() {
+        use super::is_nested;
+
+        assert!(is_nested(Some("1".into())));
+        assert!(!is_nested(Some("0".into())));
+        assert!(!is_nested(Some("".into())));
+        assert!(!is_nested(None));
+    }
+}
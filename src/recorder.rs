@@ -1,136 +1,1597 @@
-use crate::format;
+use crate::format::{self, raw};
+use crate::keys;
 use crate::pty;
 use std::collections::HashMap;
+use std::env;
 use std::io;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub struct Recorder {
     writer: Option<Box<dyn format::Writer + Send>>,
+    clock: Box<dyn Clock>,
     start_time: Instant,
+    /// When recording got paused, the timeline stands still until it's
+    /// resumed.
+    paused_at: Option<Instant>,
     append: bool,
+    /// Size of the terminal as last recorded, or at the end of the recording
+    /// being appended to.
+    previous_size: Option<(u16, u16)>,
+    record_output: bool,
     record_input: bool,
-    idle_time_limit: Option<f32>,
-    command: Option<String>,
-    title: Option<String>,
-    env: HashMap<String, String>,
+    record_unechoed_input: bool,
+    key_decoder: Option<keys::Decoder>,
+    clear_screen: bool,
+    batch_time: f64,
+    frame_rate: Option<f64>,
+    metadata: Metadata,
+    pending_output: Option<(f64, Vec<u8>)>,
+    incomplete_char: Vec<u8>,
+    alt_screen: AltScreen,
+    binary: BinarySampler,
     sender: mpsc::Sender<Message>,
     receiver: Option<mpsc::Receiver<Message>>,
     handle: Option<JoinHandle>,
+    /// Set by the writer thread once writing fails, e.g. for lack of space.
+    failed: Arc<AtomicBool>,
+    metrics: Metrics,
 }
 
+/// Follows switches between the primary and the alternate screen in the
+/// output. Switching sequences may be split between reads from the pty, so
+/// the end of the previous chunk is kept to be scanned again with the next
+/// one.
+#[derive(Default)]
+struct AltScreen {
+    active: bool,
+    tail: Vec<u8>,
+}
+
+/// Estimates how much of the output is binary data rather than text and
+/// terminal control sequences, by looking at the beginning of each chunk of
+/// it. Such data gets escaped in asciicast files, taking several times its
+/// size.
+#[derive(Default)]
+struct BinarySampler {
+    chars: usize,
+    binary: usize,
+}
+
+/// Source of time for the recorder, replaceable to make timing deterministic.
+pub trait Clock {
+    fn now(&self) -> Instant;
+    /// Current time as seconds since the epoch.
+    fn timestamp(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+#[derive(Default)]
+pub struct Metadata {
+    pub idle_time_limit: Option<f32>,
+    pub command: Option<String>,
+    pub title: Option<String>,
+    pub env: HashMap<String, String>,
+    /// Working directory of the recorded command, if it's to be recorded.
+    pub cwd: Option<String>,
+    pub theme: Option<format::asciicast::Theme>,
+    /// TERM of the user's terminal when the recorded command got a different
+    /// one.
+    pub original_term: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metrics {
+    pub cols: u16,
+    pub rows: u16,
+    pub duration: f64,
+    pub output_events: usize,
+    pub input_events: usize,
+    pub resize_events: usize,
+    pub marker_events: usize,
+    pub exit_events: usize,
+    pub key_events: usize,
+}
+
+impl Metrics {
+    /// Number of all events written, whatever their kind.
+    pub fn events(&self) -> usize {
+        self.output_events
+            + self.input_events
+            + self.resize_events
+            + self.marker_events
+            + self.exit_events
+            + self.key_events
+    }
+}
+
+/// How a `Recorder` records. `Settings::default()` starts a fresh recording of
+/// output only, each chunk of it as a separate event.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Continue an existing recording instead of starting with a header.
+    pub append: bool,
+    pub record_output: bool,
+    pub record_input: bool,
+    /// Record input typed while the terminal doesn't echo it, e.g. passwords.
+    pub record_unechoed_input: bool,
+    /// Also decode recorded input into names of the pressed keys, each
+    /// written as a separate event.
+    pub record_key_names: bool,
+    /// Start a fresh recording with an output event clearing the screen, so
+    /// that playback doesn't depend on what was on it before.
+    pub clear_screen: bool,
+    /// Merge output arriving within this many seconds into one event.
+    pub batch_time: f64,
+    /// Time all events to the next tick of a clock running at this many Hz,
+    /// merging output between ticks into one event, which makes `batch_time`
+    /// irrelevant.
+    pub frame_rate: Option<f64>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            append: false,
+            record_output: true,
+            record_input: false,
+            record_unechoed_input: true,
+            record_key_names: false,
+            clear_screen: false,
+            batch_time: 0.0,
+            frame_rate: None,
+        }
+    }
+}
+
+/// Options of `record`. `Options::default()` records output only, in a pty of
+/// the same size as the user's terminal.
+#[derive(Default)]
+pub struct Options {
+    /// Size of the pty, at least one is required when there's no terminal.
+    pub cols: Option<u16>,
+    pub rows: Option<u16>,
+    pub record_input: bool,
+    /// Metadata of the recording, its `command` is filled in by `record`.
+    pub metadata: Metadata,
+}
+
+pub struct Summary {
+    pub exit_status: pty::ExitStatus,
+    pub metrics: Metrics,
+}
+
+/// Sink shared with the writer thread, so it can be given back afterwards.
+struct SharedSink<W>(Arc<Mutex<W>>);
+
 enum Message {
     Output(f64, Vec<u8>),
     Input(f64, Vec<u8>),
     Resize(f64, (u16, u16)),
+    Exit(f64, i32),
+    Marker(f64, Option<String>),
+    Key(f64, String),
 }
 
-struct JoinHandle(Option<thread::JoinHandle<()>>);
+struct JoinHandle(Option<thread::JoinHandle<io::Result<()>>>);
 
 impl Recorder {
     pub fn new(
         writer: Box<dyn format::Writer + Send>,
-        append: bool,
-        record_input: bool,
-        idle_time_limit: Option<f32>,
-        command: Option<String>,
-        title: Option<String>,
-        env: HashMap<String, String>,
+        settings: Settings,
+        mut metadata: Metadata,
+        clock: Box<dyn Clock>,
     ) -> Self {
+        let Settings {
+            append,
+            record_output,
+            record_input,
+            record_unechoed_input,
+            record_key_names,
+            clear_screen,
+            batch_time,
+            frame_rate,
+        } = settings;
+
         let (sender, receiver) = mpsc::channel();
 
+        // explicitly given empty title means no title
+        metadata.title = match metadata.title.take() {
+            Some(title) if title.is_empty() => None,
+            Some(title) => Some(title),
+            None => metadata.command.clone().or(env::var("SHELL").ok()),
+        };
+
         Recorder {
             writer: Some(writer),
-            start_time: Instant::now(),
+            start_time: clock.now(),
+            clock,
+            paused_at: None,
             append,
+            previous_size: None,
+            record_output,
             record_input,
-            idle_time_limit,
-            command,
-            title,
-            env,
+            record_unechoed_input,
+            key_decoder: record_key_names.then(keys::Decoder::default),
+            clear_screen,
+            batch_time,
+            frame_rate,
+            metadata,
+            pending_output: None,
+            incomplete_char: Vec::new(),
+            alt_screen: AltScreen::default(),
+            binary: BinarySampler::default(),
             sender,
             receiver: Some(receiver),
             handle: None,
+            failed: Arc::new(AtomicBool::new(false)),
+            metrics: Metrics::default(),
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.metadata.env
+    }
+
+    pub fn original_term(&self) -> Option<&str> {
+        self.metadata.original_term.as_deref()
+    }
+
+    /// Writes out output held back for batching, including an unfinished UTF-8
+    /// sequence. Called automatically on drop, call it earlier when metrics
+    /// need to cover everything recorded so far.
+    pub fn flush(&mut self) {
+        if !self.incomplete_char.is_empty() {
+            let data = std::mem::take(&mut self.incomplete_char);
+            self.batch_output(&data);
+        }
+
+        self.flush_pending();
+    }
+
+    /// Tells whether the output so far left the terminal in the alternate
+    /// screen, e.g. when recording of a full-screen program got cut off.
+    pub fn in_alt_screen(&self) -> bool {
+        self.alt_screen.active
+    }
+
+    /// Switches back to the primary screen if the output left the terminal
+    /// in the alternate one, so that playback ends showing the primary screen.
+    pub fn leave_alt_screen(&mut self) {
+        if self.alt_screen.active {
+            self.flush();
+            self.batch_output(LEAVE_ALT_SCREEN);
+            self.flush_pending();
+            self.alt_screen = AltScreen::default();
+        }
+    }
+
+    /// Tells whether much of the output so far looks like binary data, which
+    /// is better recorded raw.
+    pub fn output_looks_binary(&self) -> bool {
+        self.binary.looks_binary()
+    }
+
+    /// Tells the size of the terminal at the end of the recording being
+    /// appended to. When the terminal has a different size now, the appended
+    /// part starts with a resize event, so that players adjust to it.
+    pub fn set_previous_size(&mut self, size: (u16, u16)) {
+        self.previous_size = Some(size);
+    }
+
+    /// Writes out whatever is pending and waits for the writer to get it all
+    /// to the sink. Unlike dropping, this tells whether that succeeded, or
+    /// why writing stopped early.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush();
+
+        // closing the channel lets the writer thread finish
+        self.sender = mpsc::channel().0;
+
+        match &mut self.handle {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+
+    /// Records exit status of the recorded command as the final event.
+    pub fn record_exit(&mut self, status: pty::ExitStatus) {
+        self.flush();
+
+        let status = match status {
+            pty::ExitStatus::Exited(status) => status,
+            pty::ExitStatus::Signaled(signal) => -signal,
+        };
+
+        let msg = Message::Exit(self.event_time(), status);
+        self.send(msg);
+    }
+
+    fn flush_pending(&mut self) {
+        if let Some((time, data)) = self.pending_output.take() {
+            self.send(Message::Output(time, data));
+        }
+    }
+
+    fn batch_output(&mut self, data: &[u8]) {
+        let time = self.event_time();
+        let constant_rate = self.frame_rate.is_some();
+        let batch_time = self.batch_time;
+
+        // with constant rate everything up to the same tick belongs together
+        let merge = |start: f64| match constant_rate {
+            true => time == start,
+            false => time - start < batch_time,
+        };
+
+        match &mut self.pending_output {
+            Some((start, pending)) if merge(*start) => {
+                pending.extend_from_slice(data);
+            }
+
+            _ => {
+                self.flush_pending();
+                self.pending_output = Some((time, data.into()));
+            }
+        }
+
+        if !constant_rate && batch_time <= 0.0 {
+            self.flush_pending();
         }
     }
 
     fn elapsed_time(&self) -> f64 {
-        self.start_time.elapsed().as_secs_f64()
+        let now = self.paused_at.unwrap_or_else(|| self.clock.now());
+
+        (now - self.start_time).as_secs_f64()
+    }
+
+    /// Time of an event happening now, which with a constant frame rate is
+    /// the time of the next tick.
+    fn event_time(&self) -> f64 {
+        let time = self.elapsed_time();
+
+        match self.frame_rate {
+            Some(rate) => (time * rate).ceil() / rate,
+            None => time,
+        }
+    }
+
+    fn send(&mut self, msg: Message) {
+        let time = match &msg {
+            Message::Output(time, _) => {
+                self.metrics.output_events += 1;
+                *time
+            }
+
+            Message::Input(time, _) => {
+                self.metrics.input_events += 1;
+                *time
+            }
+
+            Message::Resize(time, _) => {
+                self.metrics.resize_events += 1;
+                *time
+            }
+
+            Message::Exit(time, _) => {
+                self.metrics.exit_events += 1;
+                *time
+            }
+
+            Message::Marker(time, _) => {
+                self.metrics.marker_events += 1;
+                *time
+            }
+
+            Message::Key(time, _) => {
+                self.metrics.key_events += 1;
+                *time
+            }
+        };
+
+        self.metrics.duration = time;
+        let _ = self.sender.send(msg);
+        // TODO use notifier for error reporting
     }
 }
 
 impl pty::Recorder for Recorder {
+    /// Starting again, to record another command, continues the timeline of
+    /// the recording, resuming it if it was paused.
     fn start(&mut self, size: (u16, u16)) -> io::Result<()> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        if self.handle.is_some() {
+            self.set_paused(false);
+
+            if self.previous_size != Some(size) {
+                self.resize(size);
+            }
+
+            return Ok(());
+        }
+
+        let timestamp = self.clock.timestamp();
 
         let mut writer = self.writer.take().unwrap();
         let receiver = self.receiver.take().unwrap();
 
+        // appending leaves the original header, and so its timestamp, intact
         if !self.append {
             let header = format::Header {
                 cols: size.0,
                 rows: size.1,
                 timestamp,
-                idle_time_limit: self.idle_time_limit,
-                command: self.command.clone(),
-                title: self.title.clone(),
-                env: self.env.clone(),
+                idle_time_limit: self.metadata.idle_time_limit,
+                command: self.metadata.command.clone(),
+                title: self.metadata.title.clone(),
+                env: self.metadata.env.clone(),
+                cwd: self.metadata.cwd.clone(),
+                theme: self.metadata.theme.clone(),
+                original_term: self.metadata.original_term.clone(),
             };
 
             writer.header(&header)?;
         }
 
+        let failed = self.failed.clone();
+
+        // nothing gets written after the first failure, so the recording is
+        // left valid up to the last event that could be written whole
         let handle = thread::spawn(move || {
+            let mut result = Ok(());
+
             for msg in receiver {
-                match msg {
-                    Message::Output(time, data) => {
-                        let _ = writer.output(time, &data);
-                    }
-
-                    Message::Input(time, data) => {
-                        let _ = writer.input(time, &data);
-                    }
-
-                    Message::Resize(time, size) => {
-                        let _ = writer.resize(time, size);
-                    }
+                if result.is_err() {
+                    continue;
+                }
+
+                // writers get past interrupted writes themselves, with
+                // `write_all`, so there's no retrying a failed event here,
+                // which may be partly written
+                result = match msg {
+                    Message::Output(time, data) => writer.output(time, &data),
+                    Message::Input(time, data) => writer.input(time, &data),
+                    Message::Resize(time, size) => writer.resize(time, size),
+                    Message::Exit(time, status) => writer.exit(time, status),
+                    Message::Marker(time, label) => writer.marker(time, label.as_deref()),
+                    Message::Key(time, name) => writer.key(time, &name),
+                };
+
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
                 }
             }
+
+            // flushing again writes nothing twice, unlike writing an event
+            let flushed = pty::retry_interrupted(|| writer.flush());
+
+            result.and(flushed)
         });
 
         self.handle = Some(JoinHandle(Some(handle)));
-        self.start_time = Instant::now();
+        self.start_time = self.clock.now();
+        self.metrics.cols = size.0;
+        self.metrics.rows = size.1;
+
+        if self.clear_screen && self.record_output && !self.append {
+            self.send(Message::Output(0.0, raw::RESET.into()));
+        }
+
+        if self.append && self.previous_size.is_some_and(|previous| previous != size) {
+            self.send(Message::Resize(0.0, size));
+        }
+
+        self.previous_size = Some(size);
 
         Ok(())
     }
 
+    /// Multi-byte UTF-8 sequences may be split between reads from the pty, so
+    /// an unfinished one at the end of `data` is held back until the rest of
+    /// it arrives, keeping every event valid UTF-8.
     fn output(&mut self, data: &[u8]) {
-        let msg = Message::Output(self.elapsed_time(), data.into());
-        let _ = self.sender.send(msg);
-        // TODO use notifier for error reporting
+        if !self.record_output {
+            return;
+        }
+
+        self.alt_screen.update(data);
+        let mut data = [std::mem::take(&mut self.incomplete_char).as_slice(), data].concat();
+        let n = incomplete_char_len(&data);
+        self.incomplete_char = data.split_off(data.len() - n);
+
+        if !data.is_empty() {
+            self.binary.update(&data);
+            self.batch_output(&data);
+        }
     }
 
-    fn input(&mut self, data: &[u8]) {
-        if self.record_input {
-            let msg = Message::Input(self.elapsed_time(), data.into());
-            let _ = self.sender.send(msg);
-            // TODO use notifier for error reporting
+    fn input(&mut self, data: &[u8], echo_suppressed: bool) {
+        if self.record_input && (self.record_unechoed_input || !echo_suppressed) {
+            self.flush_pending();
+            let time = self.event_time();
+            self.send(Message::Input(time, data.into()));
+
+            if let Some(decoder) = &mut self.key_decoder {
+                for name in decoder.decode(data) {
+                    self.send(Message::Key(time, name));
+                }
+            }
         }
     }
 
     fn resize(&mut self, size: (u16, u16)) {
-        let msg = Message::Resize(self.elapsed_time(), size);
-        let _ = self.sender.send(msg);
-        // TODO use notifier for error reporting
+        self.previous_size = Some(size);
+        self.flush_pending();
+        let msg = Message::Resize(self.event_time(), size);
+        self.send(msg);
+    }
+
+    fn marker(&mut self, label: Option<&str>) {
+        self.flush_pending();
+        let msg = Message::Marker(self.event_time(), label.map(String::from));
+        self.send(msg);
+    }
+
+    /// Output held back for batching is written out on pause, so that it
+    /// doesn't get merged with output after resuming.
+    fn set_paused(&mut self, paused: bool) {
+        match (self.paused_at, paused) {
+            (None, true) => {
+                self.flush();
+                self.paused_at = Some(self.clock.now());
+            }
+
+            (Some(paused_at), false) => {
+                self.start_time += self.clock.now() - paused_at;
+                self.paused_at = None;
+            }
+
+            _ => (),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Output merged for a frame gets written once its tick comes.
+    fn tick(&mut self) -> Option<Duration> {
+        self.frame_rate?;
+        let time = self.pending_output.as_ref()?.0;
+        let elapsed = self.elapsed_time();
+
+        if elapsed >= time {
+            self.flush_pending();
+
+            None
+        } else {
+            Some(Duration::from_secs_f64(time - elapsed))
+        }
+    }
+}
+
+/// Records `command`, run with the shell, into `sink` as asciicast until it
+/// exits. The session is shown on the terminal, if there's one, the same way
+/// `rec` shows it.
+///
+/// `sink` is given back once everything has been written to it, so e.g. a
+/// recording made into a `Vec<u8>` can be used right away.
+pub fn record<W>(command: &str, options: Options, sink: W) -> anyhow::Result<(Summary, W)>
+where
+    W: io::Write + Send + 'static,
+{
+    let sink = Arc::new(Mutex::new(sink));
+    let writer = format::asciicast::Writer::new(SharedSink(sink.clone()), 0.0);
+    let mut metadata = options.metadata;
+    metadata.command = Some(command.to_owned());
+
+    let mut recorder = Recorder::new(
+        Box::new(writer),
+        Settings {
+            record_input: options.record_input,
+            // rec's default --batch-time
+            batch_time: 0.005,
+            ..Default::default()
+        },
+        metadata,
+        Box::new(SystemClock),
+    );
+
+    let exit_status = pty::exec(
+        &pty::build_exec_args(Some(command.to_owned()), None),
+        &pty::build_exec_env(None),
+        (
+            options.cols.map(pty::Dimension::Fixed),
+            options.rows.map(pty::Dimension::Fixed),
+        ),
+        None,
+        pty::DEFAULT_BUF_SIZE,
+        None,
+        &mut recorder,
+    )?
+    .status;
+
+    let metrics = recorder.metrics().clone();
+
+    // waits for the writer thread, which holds the other reference to sink
+    recorder.finish()?;
+
+    let sink = match Arc::try_unwrap(sink) {
+        Ok(sink) => sink.into_inner().unwrap_or_else(|e| e.into_inner()),
+        Err(_) => unreachable!("writer thread is done"),
+    };
+
+    Ok((
+        Summary {
+            exit_status,
+            metrics,
+        },
+        sink,
+    ))
+}
+
+impl<W: io::Write> io::Write for SharedSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+const LEAVE_ALT_SCREEN: &[u8] = b"\x1b[?1049l";
+
+impl AltScreen {
+    /// Longest of the switching sequences, `ESC [ ? 1049 h`, minus one.
+    const TAIL_LEN: usize = 7;
+
+    fn update(&mut self, data: &[u8]) {
+        let data = [std::mem::take(&mut self.tail).as_slice(), data].concat();
+
+        for (i, _) in data.windows(3).enumerate().filter(|(_, w)| w == b"\x1b[?") {
+            let params = &data[i + 3..];
+            let len = params.iter().take_while(|b| b.is_ascii_digit()).count();
+
+            if matches!(&params[..len], b"47" | b"1047" | b"1049") {
+                match params.get(len) {
+                    Some(b'h') => self.active = true,
+                    Some(b'l') => self.active = false,
+                    _ => (),
+                }
+            }
+        }
+
+        self.tail = data[data.len().saturating_sub(Self::TAIL_LEN)..].to_vec();
+    }
+}
+
+impl BinarySampler {
+    /// Bytes looked at from each chunk of output.
+    const SAMPLE_LEN: usize = 1024;
+    /// Characters to look at before drawing any conclusions.
+    const MIN_CHARS: usize = 4096;
+
+    fn update(&mut self, data: &[u8]) {
+        for chunk in data[..data.len().min(Self::SAMPLE_LEN)].utf8_chunks() {
+            for c in chunk.valid().chars() {
+                self.chars += 1;
+
+                // tab, newlines, escape, bell, backspace and shift in/out are
+                // what terminals get all the time
+                if (c.is_control() && !"\t\n\r\x1b\x07\x08\x0e\x0f".contains(c)) || c == '\u{fffd}'
+                {
+                    self.binary += 1;
+                }
+            }
+
+            if !chunk.invalid().is_empty() {
+                self.chars += 1;
+                self.binary += 1;
+            }
+        }
+    }
+
+    fn looks_binary(&self) -> bool {
+        self.chars >= Self::MIN_CHARS && self.binary * 10 >= self.chars * 3
+    }
+}
+
+/// Length of the unfinished UTF-8 sequence at the end of `data`, if any.
+fn incomplete_char_len(data: &[u8]) -> usize {
+    for i in 1..=data.len().min(3) {
+        let byte = data[data.len() - i];
+
+        // continuation byte, keep looking for the leading one
+        if byte & 0xc0 == 0x80 {
+            continue;
+        }
+
+        let len = match byte {
+            0xf0.. => 4,
+            0xe0.. => 3,
+            0xc0.. => 2,
+            _ => 1,
+        };
+
+        return if len > i { i } else { 0 };
+    }
+
+    0
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn timestamp(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl JoinHandle {
+    fn join(&mut self) -> io::Result<()> {
+        match self.0.take() {
+            Some(handle) => handle.join().expect("Thread panicked"),
+            None => Ok(()),
+        }
     }
 }
 
 impl Drop for JoinHandle {
     fn drop(&mut self) {
-        self.0.take().unwrap().join().expect("Thread panicked");
+        let _ = self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Metadata, Recorder, Settings, SharedSink};
+    use crate::format;
+    use crate::pty::{ExitStatus, Recorder as _};
+    use nix::libc;
+    use std::cell::Cell;
+    use std::io;
+    use std::rc::Rc;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[derive(Clone, Default)]
+    struct TestWriter {
+        events: Arc<Mutex<Vec<(f64, String)>>>,
+        timestamp: Arc<Mutex<Option<u64>>>,
+    }
+
+    impl TestWriter {
+        fn push(&mut self, time: f64, code: &str, data: &[u8]) {
+            let event = format!("{code}:{}", String::from_utf8_lossy(data));
+            self.events.lock().unwrap().push((time, event));
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.events
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, e)| e.clone())
+                .collect()
+        }
+
+        fn times(&self) -> Vec<f64> {
+            self.events
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(t, _)| *t)
+                .collect()
+        }
+    }
+
+    impl format::Writer for TestWriter {
+        fn header(&mut self, header: &format::Header) -> io::Result<()> {
+            *self.timestamp.lock().unwrap() = Some(header.timestamp);
+
+            Ok(())
+        }
+
+        fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+            self.push(time, "o", data);
+
+            Ok(())
+        }
+
+        fn input(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+            self.push(time, "i", data);
+
+            Ok(())
+        }
+
+        fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()> {
+            self.push(time, "r", format!("{}x{}", size.0, size.1).as_bytes());
+
+            Ok(())
+        }
+
+        fn exit(&mut self, time: f64, status: i32) -> io::Result<()> {
+            self.push(time, "x", status.to_string().as_bytes());
+
+            Ok(())
+        }
+
+        fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()> {
+            self.push(time, "m", label.unwrap_or_default().as_bytes());
+
+            Ok(())
+        }
+
+        fn key(&mut self, time: f64, name: &str) -> io::Result<()> {
+            self.push(time, "k", name.as_bytes());
+
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Clock which only moves when told to.
+    #[derive(Clone)]
+    struct TestClock {
+        start: Instant,
+        elapsed: Rc<Cell<Duration>>,
+    }
+
+    impl TestClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Rc::new(Cell::new(Duration::ZERO)),
+            }
+        }
+
+        fn advance(&self, millis: u64) {
+            self.elapsed
+                .set(self.elapsed.get() + Duration::from_millis(millis));
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed.get()
+        }
+
+        fn timestamp(&self) -> u64 {
+            1_700_000_000 + self.elapsed.get().as_secs()
+        }
+    }
+
+    fn recorder(writer: &TestWriter, batch_time: f64, clock: &TestClock) -> Recorder {
+        Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_input: true,
+                batch_time,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(clock.clone()),
+        )
+    }
+
+    #[test]
+    fn finish() {
+        struct FullSink;
+
+        impl io::Write for FullSink {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from_raw_os_error(libc::ENOSPC))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::Error::from_raw_os_error(libc::ENOSPC))
+            }
+        }
+
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 1.0, &TestClock::new());
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+
+        // pending output gets written too
+        assert!(recorder.finish().is_ok());
+        assert_eq!(writer.events(), vec!["o:$ "]);
+
+        let writer = format::asciicast::Writer::new(FullSink, 0.0);
+        let mut recorder = Recorder::new(
+            Box::new(writer),
+            Settings {
+                append: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+
+        assert!(recorder.finish().is_err());
+    }
+
+    #[test]
+    fn disk_full() {
+        /// Sink with room for `limit` bytes.
+        #[derive(Clone)]
+        struct SmallDisk {
+            data: Arc<Mutex<Vec<u8>>>,
+            limit: usize,
+        }
+
+        impl io::Write for SmallDisk {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let mut data = self.data.lock().unwrap();
+                let n = buf.len().min(self.limit - data.len());
+
+                if n == 0 {
+                    return Err(io::Error::from_raw_os_error(libc::ENOSPC));
+                }
+
+                data.extend_from_slice(&buf[..n]);
+
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let disk = SmallDisk {
+            data: Default::default(),
+            limit: 100,
+        };
+
+        let mut recorder = Recorder::new(
+            Box::new(format::asciicast::Writer::new(disk.clone(), 0.0)),
+            Settings::default(),
+            Metadata {
+                title: Some(String::new()),
+                ..Default::default()
+            },
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"fits");
+        recorder.output(&[b'x'; 100]);
+        recorder.output(b"fits too, but comes too late");
+
+        // the writer thread is done with all the events once finished
+        let failed = recorder.failed.clone();
+
+        assert_eq!(
+            recorder.finish().unwrap_err().raw_os_error(),
+            Some(libc::ENOSPC)
+        );
+        assert!(failed.load(Ordering::Relaxed));
+
+        let data = disk.data.lock().unwrap().clone();
+        let (_, events) = format::asciicast::open(&data[..]).unwrap();
+        let events = events.collect::<anyhow::Result<Vec<_>>>().unwrap();
+
+        // the partially written event doesn't count
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "fits");
+    }
+
+    #[test]
+    fn flush_interrupted() {
+        /// Gets interrupted on every other flush.
+        #[derive(Clone, Default)]
+        struct InterruptedWriter {
+            writer: TestWriter,
+            interrupted: Arc<Mutex<bool>>,
+        }
+
+        impl format::Writer for InterruptedWriter {
+            fn header(&mut self, header: &format::Header) -> io::Result<()> {
+                self.writer.header(header)
+            }
+
+            fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+                self.writer.output(time, data)
+            }
+
+            fn input(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+                self.writer.input(time, data)
+            }
+
+            fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()> {
+                self.writer.resize(time, size)
+            }
+
+            fn exit(&mut self, time: f64, status: i32) -> io::Result<()> {
+                self.writer.exit(time, status)
+            }
+
+            fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()> {
+                self.writer.marker(time, label)
+            }
+
+            fn key(&mut self, time: f64, name: &str) -> io::Result<()> {
+                self.writer.key(time, name)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                let mut interrupted = self.interrupted.lock().unwrap();
+                *interrupted = !*interrupted;
+
+                if *interrupted {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+
+                self.writer.flush()
+            }
+        }
+
+        let writer = InterruptedWriter::default();
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_input: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+        recorder.input(b"ls\r", false);
+        recorder.resize((100, 40));
+
+        assert!(recorder.finish().is_ok());
+        assert_eq!(writer.writer.events(), vec!["o:$ ", "i:ls\r", "r:100x40"]);
+    }
+
+    #[test]
+    fn start_again() {
+        let writer = TestWriter::default();
+        let clock = TestClock::new();
+        let mut recorder = recorder(&writer, 0.0, &clock);
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"one");
+        clock.advance(1000);
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"two");
+        clock.advance(500);
+        recorder.start((100, 30)).unwrap();
+        recorder.output(b"three");
+        drop(recorder);
+
+        assert_eq!(
+            writer.events(),
+            vec!["o:one", "o:two", "r:100x30", "o:three"]
+        );
+        assert_eq!(writer.times(), vec![0.0, 1.0, 1.5, 1.5]);
+    }
+
+    #[test]
+    fn pause() {
+        let writer = TestWriter::default();
+        let clock = TestClock::new();
+        let mut recorder = recorder(&writer, 0.0, &clock);
+
+        recorder.start((80, 24)).unwrap();
+        clock.advance(1000);
+        recorder.output(b"one");
+        recorder.set_paused(true);
+        clock.advance(30000);
+        recorder.resize((100, 30));
+        recorder.set_paused(false);
+        clock.advance(500);
+        recorder.output(b"two");
+        recorder.set_paused(true);
+        clock.advance(10000);
+        recorder.start((100, 30)).unwrap();
+        recorder.output(b"three");
+        drop(recorder);
+
+        assert_eq!(
+            writer.events(),
+            vec!["o:one", "r:100x30", "o:two", "o:three"]
+        );
+        assert_eq!(writer.times(), vec![1.0, 1.0, 1.5, 1.5]);
+    }
+
+    #[test]
+    fn append_at_new_size() {
+        let record = |size| {
+            let writer = TestWriter::default();
+            let mut recorder = Recorder::new(
+                Box::new(writer.clone()),
+                Settings {
+                    append: true,
+                    ..Default::default()
+                },
+                Metadata::default(),
+                Box::new(TestClock::new()),
+            );
+
+            recorder.set_previous_size((80, 24));
+            recorder.start(size).unwrap();
+            recorder.output(b"$ ");
+            drop(recorder);
+
+            writer.events()
+        };
+
+        assert_eq!(record((100, 30)), vec!["r:100x30", "o:$ "]);
+        assert_eq!(record((80, 24)), vec!["o:$ "]);
+    }
+
+    #[test]
+    fn default_title() {
+        let writer = TestWriter::default();
+        let title = |command: Option<&str>, title: Option<&str>| {
+            let metadata = Metadata {
+                command: command.map(String::from),
+                title: title.map(String::from),
+                ..Default::default()
+            };
+
+            Recorder::new(
+                Box::new(writer.clone()),
+                Settings::default(),
+                metadata,
+                Box::new(TestClock::new()),
+            )
+            .metadata
+            .title
+            .clone()
+        };
+
+        assert_eq!(title(Some("vim"), None).as_deref(), Some("vim"));
+        assert_eq!(title(Some("vim"), Some("Demo")).as_deref(), Some("Demo"));
+        assert_eq!(title(Some("vim"), Some("")), None);
+        assert_eq!(title(None, None), std::env::var("SHELL").ok());
+    }
+
+    #[test]
+    fn batching() {
+        let writer = TestWriter::default();
+        let clock = TestClock::new();
+        let mut recorder = recorder(&writer, 0.05, &clock);
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"foo");
+        clock.advance(20);
+        recorder.output(b"bar");
+        clock.advance(40);
+        recorder.output(b"baz");
+        clock.advance(49);
+        recorder.output(b"qux");
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:foobar", "o:bazqux"]);
+        assert_eq!(writer.times(), vec![0.0, 0.06]);
+    }
+
+    #[test]
+    fn timing() {
+        let writer = TestWriter::default();
+        let clock = TestClock::new();
+        clock.advance(5000);
+        let mut recorder = recorder(&writer, 0.0, &clock);
+
+        clock.advance(1000);
+        recorder.start((80, 24)).unwrap();
+        clock.advance(250);
+        recorder.output(b"foo");
+        clock.advance(1250);
+        recorder.input(b"x", false);
+        recorder.resize((100, 40));
+        drop(recorder);
+
+        assert_eq!(*writer.timestamp.lock().unwrap(), Some(1_700_000_006));
+        assert_eq!(writer.times(), vec![0.25, 1.5, 1.5]);
+    }
+
+    #[test]
+    fn constant_rate() {
+        let writer = TestWriter::default();
+        let clock = TestClock::new();
+
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_input: true,
+                frame_rate: Some(4.0),
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(clock.clone()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        assert_eq!(recorder.tick(), None);
+
+        clock.advance(100);
+        recorder.output(b"a");
+        assert_eq!(recorder.tick(), Some(Duration::from_millis(150)));
+
+        clock.advance(100);
+        recorder.output(b"b");
+        clock.advance(100);
+        assert_eq!(recorder.tick(), None);
+        assert_eq!(recorder.metrics().output_events, 1);
+
+        clock.advance(100);
+        recorder.output(b"c");
+        clock.advance(50);
+        recorder.input(b"x", false);
+        clock.advance(650);
+        recorder.output(b"d");
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:ab", "o:c", "i:x", "o:d"]);
+        assert_eq!(writer.times(), vec![0.25, 0.5, 0.5, 1.25]);
+    }
+
+    #[test]
+    fn no_batching() {
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 0.0, &TestClock::new());
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"foo");
+        recorder.output(b"bar");
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:foo", "o:bar"]);
+    }
+
+    #[test]
+    fn split_utf8_char() {
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 0.0, &TestClock::new());
+        let emoji = "🦀".as_bytes();
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(&[b"a", &emoji[..1]].concat());
+        recorder.output(&emoji[1..3]);
+        recorder.resize((100, 40));
+        recorder.output(&[&emoji[3..], b"b"].concat());
+        recorder.output(&emoji[..2]);
+        drop(recorder);
+
+        // unfinished sequence at the very end is written out as is
+        assert_eq!(
+            writer.events(),
+            vec!["o:a", "r:100x40", "o:🦀b", "o:\u{fffd}"]
+        );
+    }
+
+    #[test]
+    fn incomplete_char_len() {
+        use super::incomplete_char_len;
+
+        let emoji = "🦀".as_bytes();
+
+        assert_eq!(incomplete_char_len(b""), 0);
+        assert_eq!(incomplete_char_len(b"abc"), 0);
+        assert_eq!(incomplete_char_len(emoji), 0);
+        assert_eq!(incomplete_char_len(&emoji[..1]), 1);
+        assert_eq!(incomplete_char_len(&emoji[..3]), 3);
+        assert_eq!(incomplete_char_len("é".as_bytes()), 0);
+        assert_eq!(incomplete_char_len(&"é".as_bytes()[..1]), 1);
+    }
+
+    #[test]
+    fn record_exit() {
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 1.0, &TestClock::new());
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"bye");
+        recorder.record_exit(ExitStatus::Exited(3));
+        recorder.record_exit(ExitStatus::Signaled(9));
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:bye", "x:3", "x:-9"]);
+    }
+
+    #[test]
+    fn unechoed_input() {
+        let writer = TestWriter::default();
+
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_input: true,
+                record_unechoed_input: false,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"Password: ");
+        recorder.input(b"secret\r", true);
+        recorder.output(b"\r\n$ ");
+        recorder.input(b"ls\r", false);
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:Password: ", "o:\r\n$ ", "i:ls\r"]);
+    }
+
+    #[test]
+    fn clear_screen() {
+        let writer = TestWriter::default();
+
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                clear_screen: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:\x1b[2J\x1b[H", "o:$ "]);
+        assert_eq!(writer.times(), vec![0.0, 0.0]);
+
+        // raw recordings get it the same way
+        let sink = Arc::new(Mutex::new(Vec::new()));
+
+        let mut recorder = Recorder::new(
+            Box::new(format::raw::Writer::new(SharedSink(sink.clone()))),
+            Settings {
+                clear_screen: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+        recorder.finish().unwrap();
+
+        assert_eq!(*sink.lock().unwrap(), b"\x1b[8;24;80t\x1b[2J\x1b[H$ ");
+    }
+
+    #[test]
+    fn binary_output() {
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 0.0, &TestClock::new());
+
+        recorder.start((80, 24)).unwrap();
+
+        for _ in 0..100 {
+            recorder.output("\x1b[1;32m✓\x1b[0m passed\r\n".as_bytes());
+        }
+
+        assert!(!recorder.output_looks_binary());
+
+        let binary: Vec<u8> = (0..=255).collect();
+
+        for _ in 0..100 {
+            recorder.output(&binary);
+        }
+
+        assert!(recorder.output_looks_binary());
+    }
+
+    #[test]
+    fn alt_screen() {
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 0.0, &TestClock::new());
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ vim\r\n\x1b[?1049h\x1b[H");
+        assert!(recorder.in_alt_screen());
+
+        recorder.output(b"\x1b[?10");
+        recorder.output(b"49l$ ");
+        assert!(!recorder.in_alt_screen());
+
+        recorder.output(b"\x1b[?1049");
+        recorder.output(b"h~");
+        assert!(recorder.in_alt_screen());
+
+        recorder.leave_alt_screen();
+        assert!(!recorder.in_alt_screen());
+
+        // nothing to leave anymore
+        recorder.leave_alt_screen();
+        drop(recorder);
+
+        let events = writer.events();
+
+        assert_eq!(events.last().unwrap(), "o:\x1b[?1049l");
+        assert_eq!(events.iter().filter(|e| *e == "o:\x1b[?1049l").count(), 1);
+    }
+
+    #[test]
+    fn key_names() {
+        let writer = TestWriter::default();
+
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_input: true,
+                record_unechoed_input: false,
+                record_key_names: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.input(b"ls\r", false);
+        recorder.input(b"secret\r", true);
+        recorder.input(b"\x1b[A\x03", false);
+        drop(recorder);
+
+        assert_eq!(
+            writer.events(),
+            vec![
+                "i:ls\r",
+                "k:l",
+                "k:s",
+                "k:Enter",
+                "i:\x1b[A\x03",
+                "k:Up",
+                "k:C-c"
+            ]
+        );
+    }
+
+    #[test]
+    fn bracketed_paste() {
+        let writer = TestWriter::default();
+        let mut recorder = recorder(&writer, 0.0, &TestClock::new());
+
+        // a paste can span several reads from the tty, markers included
+        recorder.start((80, 24)).unwrap();
+        recorder.input(b"\x1b[200~echo ", false);
+        recorder.input(b"hello\x1b[201~", false);
+        drop(recorder);
+
+        assert_eq!(
+            writer.events(),
+            vec!["i:\x1b[200~echo ", "i:hello\x1b[201~"]
+        );
+    }
+
+    #[test]
+    fn marker() {
+        let writer = TestWriter::default();
+        let clock = TestClock::new();
+        let mut recorder = recorder(&writer, 1.0, &clock);
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"foo");
+        clock.advance(500);
+        recorder.marker(Some("intro"));
+        recorder.marker(None);
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["o:foo", "m:intro", "m:"]);
+        assert_eq!(writer.times(), vec![0.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn no_input() {
+        let writer = TestWriter::default();
+
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_key_names: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+        recorder.input(b"ls\r", false);
+        recorder.output(b"ls\r\n");
+        drop(recorder);
+
+        // not even key names
+        assert_eq!(writer.events(), vec!["o:$ ", "o:ls\r\n"]);
+    }
+
+    #[test]
+    fn input_only() {
+        let writer = TestWriter::default();
+
+        let mut recorder = Recorder::new(
+            Box::new(writer.clone()),
+            Settings {
+                record_output: false,
+                record_input: true,
+                ..Default::default()
+            },
+            Metadata::default(),
+            Box::new(TestClock::new()),
+        );
+
+        recorder.start((80, 24)).unwrap();
+        recorder.output(b"$ ");
+        recorder.input(b"ls\r", false);
+        recorder.output(b"ls\r\n");
+        recorder.resize((100, 40));
+        drop(recorder);
+
+        assert_eq!(writer.events(), vec!["i:ls\r", "r:100x40"]);
+    }
+
+    #[test]
+    fn record() {
+        let options = super::Options {
+            cols: Some(100),
+            rows: Some(30),
+            ..Default::default()
+        };
+
+        let (summary, sink) = super::record("printf hello; exit 3", options, Vec::new()).unwrap();
+
+        assert_eq!(summary.exit_status.code(), 3);
+        assert_eq!((summary.metrics.cols, summary.metrics.rows), (100, 30));
+
+        let (header, events) = format::asciicast::open(&sink[..]).unwrap();
+        let output = events.map(|e| e.unwrap().data).collect::<Vec<_>>().concat();
+
+        assert_eq!((header.cols, header.rows), (100, 30));
+        assert_eq!(header.command.as_deref(), Some("printf hello; exit 3"));
+        assert_eq!(output, "hello");
+    }
+
+    #[test]
+    fn record_nothing() {
+        let options = super::Options {
+            cols: Some(100),
+            rows: Some(30),
+            ..Default::default()
+        };
+
+        let (summary, sink) = super::record("true", options, Vec::new()).unwrap();
+
+        assert_eq!(summary.metrics.output_events, 0);
+
+        let (_header, events) = format::asciicast::open(&sink[..]).unwrap();
+
+        assert_eq!(events.count(), 0);
     }
 }
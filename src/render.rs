@@ -0,0 +1,88 @@
+use crate::format::asciicast::{self, Event, EventCode};
+use crate::vt;
+
+/// Replays output of a recording into a terminal of the given size, following
+/// resizes, up to time `until` (in seconds) or to the end of the recording.
+/// The terminal then holds the screen as it was displayed at that point, and
+/// the lines which scrolled off it.
+pub fn replay<I>(size: (u16, u16), events: I, until: Option<f64>) -> anyhow::Result<vt::Terminal>
+where
+    I: Iterator<Item = anyhow::Result<Event>>,
+{
+    let mut terminal = vt::Terminal::new(size.0, size.1);
+
+    for event in events {
+        let event = event?;
+
+        if until.is_some_and(|until| event.time > until) {
+            break;
+        }
+
+        match event.code {
+            EventCode::Output => terminal.feed(&event.data),
+
+            EventCode::Resize => {
+                if let Some((cols, rows)) = asciicast::parse_size(&event.data) {
+                    terminal.resize(cols, rows);
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(terminal)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::asciicast::Event;
+
+    fn replay(size: (u16, u16), output: &[&str]) -> crate::vt::Terminal {
+        let events = output
+            .iter()
+            .enumerate()
+            .map(|(i, data)| Ok(Event::output(i as f64, data.as_bytes())));
+
+        super::replay(size, events, None).unwrap()
+    }
+
+    #[test]
+    fn overwrites() {
+        let terminal = replay(
+            (20, 3),
+            &[
+                "progress: 10%",
+                "\rprogress: 50%",
+                "\r\x1b[32mprogress: done\x1b[0m\r\n",
+            ],
+        );
+
+        assert_eq!(terminal.full_ansi(), "\x1b[0;32mprogress: done\x1b[0m\n");
+    }
+
+    #[test]
+    fn cursor_positioning() {
+        let terminal = replay(
+            (20, 3),
+            &[
+                "\x1b[2J\x1b[3;5Hbottom",
+                "\x1b[1;1Htop",
+                "\x1b[2;10H\x1b[1mmiddle",
+            ],
+        );
+
+        assert_eq!(
+            terminal.full_ansi(),
+            "top\n         \x1b[0;1mmiddle\x1b[0m\n    bottom\n"
+        );
+    }
+
+    #[test]
+    fn scrollback() {
+        let terminal = replay((20, 2), &["one\r\ntwo\r\n", "three\r\nfour"]);
+
+        assert_eq!(terminal.ansi(), "three\nfour\n");
+        assert_eq!(terminal.full_ansi(), "one\ntwo\nthree\nfour\n");
+    }
+}
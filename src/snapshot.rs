@@ -0,0 +1,88 @@
+use crate::cat;
+use crate::format::asciicast;
+use crate::render;
+use crate::vt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// Prints the screen of the recording as it was at time `at` (in seconds), or
+/// at the end of the recording, as plain text or with colors if `ansi` is set.
+/// `-` reads the recording from stdin. Output goes to stdout unless `output`
+/// names a file other than `-`, which doesn't get replaced unless
+/// `overwrite` is set.
+pub fn run(
+    filename: &str,
+    at: Option<f64>,
+    ansi: bool,
+    output: Option<&str>,
+    overwrite: bool,
+    max_events: Option<usize>,
+) -> anyhow::Result<()> {
+    let reader: Box<dyn BufRead> = match filename {
+        "-" => Box::new(io::stdin().lock()),
+        _ => Box::new(io::BufReader::new(fs::File::open(filename)?)),
+    };
+
+    let terminal = screen(reader, at, max_events)?;
+    let text = if ansi {
+        terminal.ansi()
+    } else {
+        terminal.text()
+    };
+
+    match output {
+        Some(path) if path != "-" => cat::create(path, overwrite)?.write_all(text.as_bytes())?,
+        _ => io::stdout().lock().write_all(text.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+/// Replays output of the recording up to time `at` (or to its end) into a
+/// terminal of the recording's size.
+pub fn screen<R: BufRead>(
+    reader: R,
+    at: Option<f64>,
+    max_events: Option<usize>,
+) -> anyhow::Result<vt::Terminal> {
+    let (header, events) = asciicast::open(reader)?;
+
+    render::replay(
+        (header.cols, header.rows),
+        asciicast::limit(events, max_events),
+        at,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn screen() {
+        let data = b"{\"version\": 2, \"width\": 20, \"height\": 5}\n\
+[1.0, \"o\", \"$ ls\\r\\n\"]\n\
+[2.0, \"o\", \"\\u001b[31mfoo\\u001b[0m  bar\\r\\n$ \"]\n\
+[3.0, \"o\", \"\\u001b[2J\\u001b[Hcleared\"]\n";
+
+        let terminal = super::screen(&data[..], Some(2.5), None).unwrap();
+
+        assert_eq!(terminal.text(), "$ ls\nfoo  bar\n$\n");
+        assert_eq!(terminal.cursor(), (2, 2));
+        assert_eq!(terminal.ansi(), "$ ls\n\x1b[0;31mfoo\x1b[0m  bar\n$\n");
+
+        let terminal = super::screen(&data[..], None, None).unwrap();
+
+        assert_eq!(terminal.text(), "cleared\n");
+    }
+
+    #[test]
+    fn screen_resized() {
+        let data = b"{\"version\": 2, \"width\": 10, \"height\": 3}\n\
+[1.0, \"r\", \"5x3\"]\n\
+[2.0, \"o\", \"abcdefgh\"]\n";
+
+        let terminal = super::screen(&data[..], None, None).unwrap();
+
+        assert_eq!(terminal.size(), (5, 3));
+        assert_eq!(terminal.text(), "abcde\nfgh\n");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::format::{self, asciicast, Writer};
+use std::fs;
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// Serves a recording live to clients of a Unix socket, as an asciicast
+/// stream. Clients get the header first, whenever they connect, followed by
+/// events recorded after they connected.
+///
+/// Clients get taken in as events are written, and the ones which can't keep
+/// up get disconnected, so that they never hold up the recording.
+pub struct Server {
+    path: PathBuf,
+    listener: UnixListener,
+    /// Encodes events, which are then taken from it and sent out.
+    encoder: asciicast::Writer<Vec<u8>>,
+    header: Option<Vec<u8>>,
+    clients: Vec<UnixStream>,
+}
+
+impl Server {
+    /// Creates the socket at `path`. The socket gets removed on drop, and the
+    /// clients disconnected.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            listener,
+            encoder: asciicast::Writer::new(Vec::new(), 0.0),
+            header: None,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Takes in clients which connected since the last event.
+    fn accept(&mut self) {
+        while let Ok((mut stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+
+            match &self.header {
+                Some(header) if stream.write_all(header).is_err() => (),
+                _ => self.clients.push(stream),
+            }
+        }
+    }
+
+    /// Sends what `encode` writes to the encoder to all clients, returning it.
+    fn send<F>(&mut self, encode: F) -> io::Result<Vec<u8>>
+    where
+        F: FnOnce(&mut asciicast::Writer<Vec<u8>>) -> io::Result<()>,
+    {
+        self.accept();
+        encode(&mut self.encoder)?;
+        let data = mem::take(self.encoder.get_mut());
+
+        // a partially written event would leave the client with garbage
+        self.clients
+            .retain_mut(|stream| stream.write_all(&data).is_ok());
+
+        Ok(data)
+    }
+}
+
+impl Writer for Server {
+    fn header(&mut self, header: &format::Header) -> io::Result<()> {
+        self.header = Some(self.send(|encoder| encoder.header(header))?);
+
+        Ok(())
+    }
+
+    fn output(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        self.send(|encoder| encoder.output(time, data)).map(drop)
+    }
+
+    fn input(&mut self, time: f64, data: &[u8]) -> io::Result<()> {
+        self.send(|encoder| encoder.input(time, data)).map(drop)
+    }
+
+    fn resize(&mut self, time: f64, size: (u16, u16)) -> io::Result<()> {
+        self.send(|encoder| encoder.resize(time, size)).map(drop)
+    }
+
+    fn exit(&mut self, time: f64, status: i32) -> io::Result<()> {
+        self.send(|encoder| encoder.exit(time, status)).map(drop)
+    }
+
+    fn marker(&mut self, time: f64, label: Option<&str>) -> io::Result<()> {
+        self.send(|encoder| encoder.marker(time, label)).map(drop)
+    }
+
+    fn key(&mut self, time: f64, name: &str) -> io::Result<()> {
+        self.send(|encoder| encoder.key(time, name)).map(drop)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Server;
+    use crate::format::{Header, Writer};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn server() {
+        let dir = crate::test_util::TempDir::new();
+        let path = dir.join("socket");
+        let mut server = Server::bind(&path).unwrap();
+
+        let header = Header {
+            cols: 80,
+            rows: 24,
+            timestamp: 0,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: HashMap::new(),
+            cwd: None,
+            theme: None,
+            original_term: None,
+        };
+
+        // connected clients get taken in with the next event
+        let early = UnixStream::connect(&path).unwrap();
+        server.header(&header).unwrap();
+        server.output(1.0, b"one").unwrap();
+
+        let late = UnixStream::connect(&path).unwrap();
+        server.output(2.0, b"two").unwrap();
+        drop(server);
+
+        let lines = |stream| {
+            BufReader::new(stream)
+                .lines()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        };
+
+        let header = r#"{"version":2,"width":80,"height":24,"timestamp":0}"#;
+        let one = r#"[1.0,"o","one"]"#;
+        let two = r#"[2.0,"o","two"]"#;
+
+        assert_eq!(lines(early), vec![header, one, two]);
+        assert_eq!(lines(late), vec![header, two]);
+        assert!(!path.exists());
+    }
+}
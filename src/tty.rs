@@ -0,0 +1,211 @@
+use nix::sys::signal::{self, Signal};
+use nix::sys::termios::{self, SetArg, Termios};
+use nix::unistd;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::panic;
+use std::sync::{Mutex, Once};
+
+/// Shows the cursor and switches cursor keys and keypad back to normal mode,
+/// in case the recorded program changed them.
+const RESET_MODES: &[u8] = b"\x1b[?25h\x1b[?1l\x1b>";
+
+/// Terminals currently in raw mode, with their original settings, for the
+/// panic hook to restore.
+static ACTIVE: Mutex<Vec<(RawFd, Termios)>> = Mutex::new(Vec::new());
+static PANIC_HOOK: Once = Once::new();
+
+/// Keeps the terminal in raw mode while alive, restoring the original mode
+/// when dropped. The mode is set on the given terminal itself, whatever
+/// stdin and stdout happen to be.
+///
+/// A panic restores the terminal right away, before the panic message gets
+/// printed, so it's readable, and the terminal is usable even if the process
+/// dies without dropping the guard.
+pub struct RawMode<F: AsFd> {
+    file: F,
+    original: Termios,
+}
+
+impl<F: AsFd> RawMode<F> {
+    pub fn new(file: F) -> io::Result<Self> {
+        let original = termios::tcgetattr(file.as_fd())?;
+        let mut raw = original.clone();
+        termios::cfmakeraw(&mut raw);
+
+        PANIC_HOOK.call_once(install_panic_hook);
+        termios::tcsetattr(file.as_fd(), SetArg::TCSANOW, &raw)?;
+        lock_active().push((file.as_fd().as_raw_fd(), original.clone()));
+
+        Ok(Self { file, original })
+    }
+}
+
+impl<F: AsFd> Drop for RawMode<F> {
+    fn drop(&mut self) {
+        let fd = self.file.as_fd().as_raw_fd();
+        lock_active().retain(|(active, _)| *active != fd);
+        let _ = termios::tcsetattr(self.file.as_fd(), SetArg::TCSANOW, &self.original);
+    }
+}
+
+/// Stops the process, the way Ctrl-Z would in a shell. Terminals in raw mode
+/// are restored while stopped, and put back into raw mode once the process
+/// gets continued.
+pub fn suspend() -> io::Result<()> {
+    suspend_while(|| signal::raise(Signal::SIGSTOP).map_err(io::Error::from))
+}
+
+fn suspend_while(stop: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+    let active = lock_active().clone();
+    let mut raw = Vec::with_capacity(active.len());
+
+    for (fd, original) in &active {
+        let fd = unsafe { BorrowedFd::borrow_raw(*fd) };
+        raw.push(termios::tcgetattr(fd)?);
+        termios::tcsetattr(fd, SetArg::TCSANOW, original)?;
+    }
+
+    let result = stop();
+
+    for ((fd, _), raw) in active.iter().zip(raw) {
+        let fd = unsafe { BorrowedFd::borrow_raw(*fd) };
+        termios::tcsetattr(fd, SetArg::TCSANOW, &raw)?;
+    }
+
+    result
+}
+
+fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        for (fd, original) in lock_active().drain(..) {
+            // the guards owning these fds are still alive, as they're only
+            // dropped during unwinding, which comes later
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            let _ = termios::tcsetattr(fd, SetArg::TCSANOW, &original);
+            let _ = unistd::write(fd.as_raw_fd(), RESET_MODES);
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// A panic while holding the lock must not stop the terminal from being
+/// restored.
+fn lock_active() -> std::sync::MutexGuard<'static, Vec<(RawFd, Termios)>> {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<F: AsFd> Deref for RawMode<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.file
+    }
+}
+
+impl<F: AsFd> DerefMut for RawMode<F> {
+    fn deref_mut(&mut self) -> &mut F {
+        &mut self.file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawMode;
+    use nix::pty::openpty;
+    use nix::sys::termios::{self, LocalFlags};
+    use std::os::fd::AsRawFd;
+    use std::panic;
+
+    #[test]
+    fn raw_mode() {
+        let pty = openpty(None, None).unwrap();
+        let echo = || {
+            termios::tcgetattr(&pty.slave)
+                .unwrap()
+                .local_flags
+                .contains(LocalFlags::ECHO)
+        };
+
+        assert!(echo());
+
+        let raw = RawMode::new(&pty.slave).unwrap();
+
+        assert!(!echo());
+
+        drop(raw);
+
+        assert!(echo());
+    }
+
+    #[test]
+    fn suspend() {
+        let pty = openpty(None, None).unwrap();
+        let echo = || {
+            termios::tcgetattr(&pty.slave)
+                .unwrap()
+                .local_flags
+                .contains(LocalFlags::ECHO)
+        };
+
+        let _raw = RawMode::new(&pty.slave).unwrap();
+
+        super::suspend_while(|| {
+            assert!(echo());
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(!echo());
+    }
+
+    #[test]
+    fn restore_on_panic() {
+        // the panic hook restores terminals of every test running alongside,
+        // so the panic happens in a test process of its own
+        if std::env::var_os("ASCIINEMA_TEST_PANIC").is_none() {
+            let output = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "tty::tests::restore_on_panic"])
+                .env("ASCIINEMA_TEST_PANIC", "1")
+                .output()
+                .unwrap();
+
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stdout)
+            );
+
+            return;
+        }
+
+        let pty = openpty(None, None).unwrap();
+        let echo = || {
+            termios::tcgetattr(&pty.slave)
+                .unwrap()
+                .local_flags
+                .contains(LocalFlags::ECHO)
+        };
+
+        let result = panic::catch_unwind(|| {
+            let raw = RawMode::new(&pty.slave).unwrap();
+            assert!(!echo());
+
+            // as if the process died without unwinding
+            std::mem::forget(raw);
+            panic!("copy failed");
+        });
+
+        assert!(result.is_err());
+        assert!(echo());
+
+        let mut buf = [0u8; 64];
+        let n = nix::unistd::read(pty.master.as_raw_fd(), &mut buf).unwrap();
+
+        assert_eq!(&buf[..n], super::RESET_MODES);
+    }
+}
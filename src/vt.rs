@@ -0,0 +1,898 @@
+/// In-memory terminal, rendering output of a recording into a grid of
+/// characters the way a terminal would display it, e.g. for taking snapshots
+/// of the screen.
+///
+/// It covers what's commonly used by shells and full screen programs: cursor
+/// movement, erasing, scrolling (with scroll regions), insertion and deletion,
+/// colors and text attributes, and the alternate screen. Everything else, e.g.
+/// OSC and DCS sequences, is skipped. All characters are assumed to take a
+/// single cell.
+#[derive(Debug, Clone)]
+pub struct Terminal {
+    cols: usize,
+    rows: usize,
+    lines: Vec<Vec<Cell>>,
+    /// Lines scrolled off the top of the primary screen, oldest first.
+    scrollback: Vec<Vec<Cell>>,
+    /// Lines of the primary screen while the alternate one is active.
+    primary: Option<Vec<Vec<Cell>>>,
+    cursor: Cursor,
+    saved_cursor: Option<Cursor>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub pen: Pen,
+}
+
+/// Colors and attributes of text.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pen {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub faint: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Cursor {
+    col: usize,
+    row: usize,
+    pen: Pen,
+    /// Set after printing in the last column, the next character then goes
+    /// to the next line.
+    wrap_pending: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    Csi {
+        params: String,
+        intermediates: String,
+    },
+    /// OSC, DCS, SOS, PM or APC, terminated with ST (or BEL for OSC)
+    String,
+    StringEscape,
+}
+
+impl Terminal {
+    pub fn new(cols: u16, rows: u16) -> Self {
+        let cols = (cols as usize).max(1);
+        let rows = (rows as usize).max(1);
+
+        Self {
+            cols,
+            rows,
+            lines: vec![vec![Cell::default(); cols]; rows],
+            scrollback: Vec::new(),
+            primary: None,
+            cursor: Cursor::default(),
+            saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            state: State::default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn size(&self) -> (u16, u16) {
+        (self.cols as u16, self.rows as u16)
+    }
+
+    /// Position of the cursor as (column, row), counted from 0.
+    #[cfg(test)]
+    pub fn cursor(&self) -> (u16, u16) {
+        (self.cursor.col as u16, self.cursor.row as u16)
+    }
+
+    #[cfg(test)]
+    pub fn lines(&self) -> &[Vec<Cell>] {
+        &self.lines
+    }
+
+    /// Resizes the screen, cutting lines off at the top when it gets lower
+    /// than the cursor position, like terminals do.
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        let cols = (cols as usize).max(1);
+        let rows = (rows as usize).max(1);
+
+        let cut = (self.cursor.row + 1).saturating_sub(rows);
+        let cut_lines = self.lines.drain(..cut);
+
+        match &mut self.primary {
+            None => self.scrollback.extend(cut_lines),
+
+            Some(primary) => {
+                drop(cut_lines);
+                primary.drain(..cut);
+            }
+        }
+
+        for lines in [Some(&mut self.lines), self.primary.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            lines.resize(rows, vec![Cell::default(); cols]);
+
+            for line in lines.iter_mut() {
+                line.resize(cols, Cell::default());
+            }
+        }
+
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.col = self.cursor.col.min(cols - 1);
+        self.cursor.wrap_pending = false;
+        self.cols = cols;
+        self.rows = rows;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+    }
+
+    pub fn feed(&mut self, data: &str) {
+        for ch in data.chars() {
+            self.feed_char(ch);
+        }
+    }
+
+    /// Screen content as plain text, without trailing whitespace on lines
+    /// and without trailing empty lines.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+
+        for line in trimmed_lines(&self.lines, |cell| cell.ch == ' ') {
+            text.extend(line.iter().map(|cell| cell.ch));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Screen content as text with SGR sequences setting colors and text
+    /// attributes, e.g. for printing in another terminal. Each line ends with
+    /// the attributes reset.
+    pub fn ansi(&self) -> String {
+        ansi(trimmed_lines(&self.lines, Cell::is_blank))
+    }
+
+    /// The same as `ansi`, preceded by the scrollback, i.e. everything that
+    /// was displayed on the primary screen.
+    pub fn full_ansi(&self) -> String {
+        let lines = self.scrollback.iter().chain(&self.lines);
+
+        ansi(trimmed_lines(lines, Cell::is_blank))
+    }
+
+    fn feed_char(&mut self, ch: char) {
+        let state = std::mem::take(&mut self.state);
+
+        self.state = match (state, ch) {
+            (State::StringEscape, '\\') => State::Ground,
+            (State::String, '\x07') => State::Ground,
+            (State::String, '\x1b') => State::StringEscape,
+            (State::String, _) => State::String,
+
+            (_, '\x1b') => State::Escape,
+            (_, '\x18' | '\x1a') => State::Ground,
+
+            (State::Ground, ch) if ch < ' ' || ch == '\x7f' => {
+                self.control(ch);
+                State::Ground
+            }
+
+            (State::Ground, ch) => {
+                self.print(ch);
+                State::Ground
+            }
+
+            (State::Escape | State::StringEscape, '[') => State::Csi {
+                params: String::new(),
+                intermediates: String::new(),
+            },
+
+            (State::Escape | State::StringEscape, ']' | 'P' | 'X' | '^' | '_') => State::String,
+            (State::Escape | State::StringEscape, ' '..='/') => State::EscapeIntermediate,
+            (State::EscapeIntermediate, ' '..='/') => State::EscapeIntermediate,
+
+            (State::Escape | State::StringEscape, ch) => {
+                self.escape(ch);
+                State::Ground
+            }
+
+            // charset designation and the like
+            (State::EscapeIntermediate, _) => State::Ground,
+
+            // control characters get executed in the middle of sequences too
+            (state @ State::Csi { .. }, ch) if ch < ' ' => {
+                self.control(ch);
+                state
+            }
+
+            (
+                State::Csi {
+                    mut params,
+                    intermediates,
+                },
+                '0'..='?',
+            ) if intermediates.is_empty() => {
+                params.push(ch);
+
+                State::Csi {
+                    params,
+                    intermediates,
+                }
+            }
+
+            (
+                State::Csi {
+                    params,
+                    mut intermediates,
+                },
+                ' '..='/',
+            ) => {
+                intermediates.push(ch);
+
+                State::Csi {
+                    params,
+                    intermediates,
+                }
+            }
+
+            (
+                State::Csi {
+                    params,
+                    intermediates,
+                },
+                '@'..='~',
+            ) => {
+                if intermediates.is_empty() {
+                    self.csi(&params, ch);
+                }
+
+                State::Ground
+            }
+
+            // malformed sequence
+            (State::Csi { .. }, _) => State::Ground,
+        };
+    }
+
+    fn control(&mut self, ch: char) {
+        match ch {
+            '\x08' => {
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+                self.cursor.wrap_pending = false;
+            }
+
+            '\t' => {
+                self.cursor.col = ((self.cursor.col / 8 + 1) * 8).min(self.cols - 1);
+                self.cursor.wrap_pending = false;
+            }
+
+            '\n' | '\x0b' | '\x0c' => self.linefeed(),
+
+            '\r' => {
+                self.cursor.col = 0;
+                self.cursor.wrap_pending = false;
+            }
+
+            _ => (),
+        }
+    }
+
+    fn escape(&mut self, ch: char) {
+        match ch {
+            '7' => self.saved_cursor = Some(self.cursor),
+            '8' => self.restore_cursor(),
+            'D' => self.linefeed(),
+
+            'E' => {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+
+            'M' => self.reverse_linefeed(),
+            'c' => *self = Terminal::new(self.cols as u16, self.rows as u16),
+            _ => (),
+        }
+    }
+
+    fn csi(&mut self, params: &str, ch: char) {
+        if let Some(params) = params.strip_prefix('?') {
+            match ch {
+                'h' => self.set_private_modes(params, true),
+                'l' => self.set_private_modes(params, false),
+                _ => (),
+            }
+
+            return;
+        }
+
+        // other private sequences, e.g. xterm's key modifier options
+        if params.starts_with(['<', '=', '>']) {
+            return;
+        }
+
+        if ch == 'm' {
+            self.sgr(params);
+            return;
+        }
+
+        let args = params
+            .split(';')
+            .map(|param| param.parse::<usize>().unwrap_or(0))
+            .collect::<Vec<_>>();
+
+        let arg = |i: usize, default: usize| match args.get(i) {
+            Some(0) | None => default,
+            Some(&n) => n,
+        };
+
+        let n = arg(0, 1);
+        let (col, row) = (self.cursor.col, self.cursor.row);
+
+        match ch {
+            '@' => self.insert_cells(n),
+            'A' => self.move_to(col, row.saturating_sub(n).max(self.top_margin())),
+            'B' | 'e' => self.move_to(col, row.saturating_add(n).min(self.bottom_margin())),
+            'C' | 'a' => self.move_to(col.saturating_add(n), row),
+            'D' => self.move_to(col.saturating_sub(n), row),
+            'E' => self.move_to(0, row.saturating_add(n).min(self.bottom_margin())),
+            'F' => self.move_to(0, row.saturating_sub(n).max(self.top_margin())),
+            'G' | '`' => self.move_to(n - 1, row),
+            'H' | 'f' => self.move_to(arg(1, 1) - 1, n - 1),
+            'd' => self.move_to(col, n - 1),
+
+            'J' => match arg(0, 0) {
+                0 => {
+                    self.erase_line(row, col, self.cols);
+                    self.erase_lines(row + 1, self.rows);
+                }
+
+                1 => {
+                    self.erase_lines(0, row);
+                    self.erase_line(row, 0, col + 1);
+                }
+
+                2 => self.erase_lines(0, self.rows),
+
+                3 => {
+                    self.erase_lines(0, self.rows);
+                    self.scrollback.clear();
+                }
+                _ => (),
+            },
+
+            'K' => match arg(0, 0) {
+                0 => self.erase_line(row, col, self.cols),
+                1 => self.erase_line(row, 0, col + 1),
+                2 => self.erase_line(row, 0, self.cols),
+                _ => (),
+            },
+
+            'L' => self.insert_lines(n),
+            'M' => self.delete_lines(n),
+            'P' => self.delete_cells(n),
+            'S' => self.scroll_up(self.scroll_top, n),
+            'T' => self.scroll_down(self.scroll_top, n),
+            'X' => self.erase_line(row, col, col.saturating_add(n)),
+
+            'r' => {
+                let top = arg(0, 1) - 1;
+                let bottom = arg(1, self.rows).min(self.rows) - 1;
+
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+
+            's' => self.saved_cursor = Some(self.cursor),
+            'u' => self.restore_cursor(),
+            _ => (),
+        }
+    }
+
+    fn set_private_modes(&mut self, params: &str, enabled: bool) {
+        for mode in params.split(';') {
+            match (mode, enabled) {
+                ("1049", true) => {
+                    self.saved_cursor = Some(self.cursor);
+                    self.switch_screen(true);
+                }
+
+                ("1049", false) => {
+                    self.switch_screen(false);
+                    self.restore_cursor();
+                }
+
+                ("47" | "1047", _) => self.switch_screen(enabled),
+                _ => (),
+            }
+        }
+    }
+
+    fn switch_screen(&mut self, alternate: bool) {
+        match (alternate, self.primary.take()) {
+            (true, None) => {
+                let blank = vec![vec![Cell::default(); self.cols]; self.rows];
+                self.primary = Some(std::mem::replace(&mut self.lines, blank));
+            }
+
+            (false, Some(primary)) => self.lines = primary,
+            (_, primary) => self.primary = primary,
+        }
+    }
+
+    fn sgr(&mut self, params: &str) {
+        let pen = &mut self.cursor.pen;
+        let mut params = params.split(';');
+
+        while let Some(param) = params.next() {
+            // colors given with subparameters, e.g. 38:2::255:0:0
+            if param.contains(':') {
+                let sub = param
+                    .split(':')
+                    .map(|p| p.parse::<u8>().unwrap_or(0))
+                    .collect::<Vec<_>>();
+
+                let color = match sub[..] {
+                    [_, 5, n] => Some(Color::Indexed(n)),
+                    [_, 2, .., r, g, b] => Some(Color::Rgb(r, g, b)),
+                    _ => None,
+                };
+
+                match sub[0] {
+                    38 => pen.fg = color,
+                    48 => pen.bg = color,
+                    4 => pen.underline = sub[1] > 0,
+                    _ => (),
+                }
+
+                continue;
+            }
+
+            let mut next = || params.next().and_then(|p| p.parse::<u8>().ok());
+
+            match param.parse::<u8>().unwrap_or(0) {
+                0 => *pen = Pen::default(),
+                1 => pen.bold = true,
+                2 => pen.faint = true,
+                3 => pen.italic = true,
+                4 => pen.underline = true,
+                5 => pen.blink = true,
+                7 => pen.inverse = true,
+
+                22 => {
+                    pen.bold = false;
+                    pen.faint = false;
+                }
+
+                23 => pen.italic = false,
+                24 => pen.underline = false,
+                25 => pen.blink = false,
+                27 => pen.inverse = false,
+                n @ 30..=37 => pen.fg = Some(Color::Indexed(n - 30)),
+                39 => pen.fg = None,
+                n @ 40..=47 => pen.bg = Some(Color::Indexed(n - 40)),
+                49 => pen.bg = None,
+                n @ 90..=97 => pen.fg = Some(Color::Indexed(n - 90 + 8)),
+                n @ 100..=107 => pen.bg = Some(Color::Indexed(n - 100 + 8)),
+
+                n @ (38 | 48) => {
+                    let color = match next() {
+                        Some(5) => next().map(Color::Indexed),
+
+                        Some(2) => match (next(), next(), next()) {
+                            (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                            _ => None,
+                        },
+
+                        _ => None,
+                    };
+
+                    if n == 38 {
+                        pen.fg = color;
+                    } else {
+                        pen.bg = color;
+                    }
+                }
+
+                _ => (),
+            }
+        }
+    }
+
+    fn print(&mut self, ch: char) {
+        if self.cursor.wrap_pending {
+            self.cursor.col = 0;
+            self.cursor.wrap_pending = false;
+            self.linefeed();
+        }
+
+        self.lines[self.cursor.row][self.cursor.col] = Cell {
+            ch,
+            pen: self.cursor.pen,
+        };
+
+        if self.cursor.col + 1 < self.cols {
+            self.cursor.col += 1;
+        } else {
+            self.cursor.wrap_pending = true;
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.cursor.wrap_pending = false;
+
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(self.scroll_top, 1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_linefeed(&mut self) {
+        self.cursor.wrap_pending = false;
+
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(self.scroll_top, 1);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        self.cursor.col = col.min(self.cols - 1);
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.wrap_pending = false;
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some(cursor) = self.saved_cursor {
+            self.cursor = cursor;
+            self.move_to(cursor.col, cursor.row);
+        }
+    }
+
+    /// Top of the scroll region if the cursor is in it, the cursor doesn't
+    /// leave the region moving up or down.
+    fn top_margin(&self) -> usize {
+        if self.cursor.row >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        }
+    }
+
+    fn bottom_margin(&self) -> usize {
+        if self.cursor.row <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            self.rows - 1
+        }
+    }
+
+    /// Blank cell, erased with the current background color.
+    fn blank(&self) -> Cell {
+        Cell {
+            ch: ' ',
+            pen: Pen {
+                bg: self.cursor.pen.bg,
+                ..Pen::default()
+            },
+        }
+    }
+
+    fn erase_line(&mut self, row: usize, from: usize, to: usize) {
+        let blank = self.blank();
+        let to = to.min(self.cols);
+
+        if from < to {
+            self.lines[row][from..to].fill(blank);
+        }
+
+        self.cursor.wrap_pending = false;
+    }
+
+    fn erase_lines(&mut self, from: usize, to: usize) {
+        for row in from..to {
+            self.erase_line(row, 0, self.cols);
+        }
+    }
+
+    /// Scrolls lines from `top` to the bottom of the scroll region up by `n`,
+    /// blank lines coming in at the bottom.
+    fn scroll_up(&mut self, top: usize, n: usize) {
+        let bottom = self.scroll_bottom + 1;
+        let n = n.min(bottom - top);
+        let blank = vec![self.blank(); self.cols];
+
+        if top == 0 && self.primary.is_none() {
+            self.scrollback.extend(self.lines[..n].iter().cloned());
+        }
+
+        self.lines[top..bottom].rotate_left(n);
+        self.lines[bottom - n..bottom].fill(blank);
+    }
+
+    fn scroll_down(&mut self, top: usize, n: usize) {
+        let bottom = self.scroll_bottom + 1;
+        let n = n.min(bottom - top);
+        let blank = vec![self.blank(); self.cols];
+
+        self.lines[top..bottom].rotate_right(n);
+        self.lines[top..top + n].fill(blank);
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if (self.scroll_top..=self.scroll_bottom).contains(&self.cursor.row) {
+            self.scroll_down(self.cursor.row, n);
+            self.cursor.col = 0;
+            self.cursor.wrap_pending = false;
+        }
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if (self.scroll_top..=self.scroll_bottom).contains(&self.cursor.row) {
+            self.scroll_up(self.cursor.row, n);
+            self.cursor.col = 0;
+            self.cursor.wrap_pending = false;
+        }
+    }
+
+    fn insert_cells(&mut self, n: usize) {
+        let blank = self.blank();
+        let col = self.cursor.col;
+        let n = n.min(self.cols - col);
+        let line = &mut self.lines[self.cursor.row];
+
+        line[col..].rotate_right(n);
+        line[col..col + n].fill(blank);
+        self.cursor.wrap_pending = false;
+    }
+
+    fn delete_cells(&mut self, n: usize) {
+        let blank = self.blank();
+        let col = self.cursor.col;
+        let n = n.min(self.cols - col);
+        let cols = self.cols;
+        let line = &mut self.lines[self.cursor.row];
+
+        line[col..].rotate_left(n);
+        line[cols - n..].fill(blank);
+        self.cursor.wrap_pending = false;
+    }
+}
+
+fn ansi(lines: Vec<&[Cell]>) -> String {
+    let mut text = String::new();
+
+    for line in lines {
+        let mut pen = Pen::default();
+
+        for cell in line {
+            if cell.pen != pen {
+                pen = cell.pen;
+                text.push_str(&pen.sgr());
+            }
+
+            text.push(cell.ch);
+        }
+
+        if pen != Pen::default() {
+            text.push_str("\x1b[0m");
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Lines up to their last cell that isn't blank, without the trailing empty
+/// ones.
+fn trimmed_lines<'a, I>(lines: I, is_blank: fn(&Cell) -> bool) -> Vec<&'a [Cell]>
+where
+    I: IntoIterator<Item = &'a Vec<Cell>>,
+{
+    let mut lines = lines
+        .into_iter()
+        .map(|line| {
+            let len = line
+                .iter()
+                .rposition(|cell| !is_blank(cell))
+                .map_or(0, |i| i + 1);
+
+            &line[..len]
+        })
+        .collect::<Vec<_>>();
+
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+
+    lines
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            ch: ' ',
+            pen: Pen::default(),
+        }
+    }
+}
+
+impl Cell {
+    fn is_blank(&self) -> bool {
+        *self == Cell::default()
+    }
+}
+
+impl Pen {
+    /// SGR sequence switching to this pen from any other.
+    fn sgr(&self) -> String {
+        let mut params = vec!["0".to_owned()];
+
+        for (set, param) in [
+            (self.bold, 1),
+            (self.faint, 2),
+            (self.italic, 3),
+            (self.underline, 4),
+            (self.blink, 5),
+            (self.inverse, 7),
+        ] {
+            if set {
+                params.push(param.to_string());
+            }
+        }
+
+        if let Some(fg) = self.fg {
+            params.push(fg.sgr(30, 90, 38));
+        }
+
+        if let Some(bg) = self.bg {
+            params.push(bg.sgr(40, 100, 48));
+        }
+
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+impl Color {
+    fn sgr(&self, base: u8, bright_base: u8, extended: u8) -> String {
+        match *self {
+            Color::Indexed(n @ 0..=7) => (base + n).to_string(),
+            Color::Indexed(n @ 8..=15) => (bright_base + n - 8).to_string(),
+            Color::Indexed(n) => format!("{};5;{}", extended, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", extended, r, g, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Terminal};
+
+    fn render(cols: u16, rows: u16, data: &str) -> Terminal {
+        let mut terminal = Terminal::new(cols, rows);
+        terminal.feed(data);
+
+        terminal
+    }
+
+    #[test]
+    fn print_and_wrap() {
+        let terminal = render(5, 4, "hello world\r\n\tx");
+
+        assert_eq!(terminal.text(), "hello\n worl\nd\n    x\n");
+        assert_eq!(terminal.cursor(), (4, 3));
+    }
+
+    #[test]
+    fn scroll() {
+        let terminal = render(10, 2, "one\r\ntwo\r\nthree");
+
+        assert_eq!(terminal.text(), "two\nthree\n");
+
+        // scroll region of the first two lines, the last one stays
+        let terminal = render(20, 3, "\x1b[3Hstatus\x1b[1;2r\x1b[2Hone\ntwo\nthree");
+
+        assert_eq!(terminal.text(), "   two\n      three\nstatus\n");
+    }
+
+    #[test]
+    fn cursor_movement_and_erasing() {
+        let terminal = render(10, 3, "abcdef\x1b[3D\x1b[KXY\x1b[2;4H!\x1b[1G\x1b[2P");
+
+        assert_eq!(terminal.text(), "abcXY\n !\n");
+
+        // erased with a background color, which only matters with colors
+        let terminal = render(10, 3, "one\x1b[41m\x1b[K\x1b[0m");
+
+        assert_eq!(terminal.text(), "one\n");
+        assert_eq!(terminal.ansi(), "one\x1b[0;41m       \x1b[0m\n");
+
+        let terminal = render(10, 3, "one\r\ntwo\r\nthree\x1b[2;2H\x1b[J");
+
+        assert_eq!(terminal.text(), "one\nt\n");
+
+        let terminal = render(10, 3, "one\r\ntwo\x1b[H\x1b[Lzero");
+
+        assert_eq!(terminal.text(), "zero\none\ntwo\n");
+
+        // counts too big to add to the cursor position get clamped
+        let max = usize::MAX;
+        let terminal = render(
+            10,
+            3,
+            &format!("a\x1b[{max}Cb\x1b[{max}Bc\x1b[H\x1b[{max}X"),
+        );
+
+        assert_eq!(terminal.text(), "\n\n         c\n");
+    }
+
+    #[test]
+    fn alternate_screen() {
+        let mut terminal = render(10, 3, "$ vim\r\n");
+        terminal.feed("\x1b[?1049h\x1b[Hediting");
+
+        assert_eq!(terminal.text(), "editing\n");
+
+        terminal.feed("\x1b[?1049l$ ");
+
+        assert_eq!(terminal.text(), "$ vim\n$\n");
+        assert_eq!(terminal.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn sgr() {
+        let terminal = render(
+            20,
+            1,
+            "\x1b[1;31mA\x1b[38;5;100;48:2::1:2:3mB\x1b[22;39;49mC",
+        );
+        let cells = &terminal.lines()[0];
+
+        assert!(cells[0].pen.bold);
+        assert_eq!(cells[0].pen.fg, Some(Color::Indexed(1)));
+        assert_eq!(cells[1].pen.fg, Some(Color::Indexed(100)));
+        assert_eq!(cells[1].pen.bg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(cells[2].pen, Default::default());
+
+        assert_eq!(
+            terminal.ansi(),
+            "\x1b[0;1;31mA\x1b[0;1;38;5;100;48;2;1;2;3mB\x1b[0mC\n"
+        );
+    }
+
+    #[test]
+    fn skipped_sequences() {
+        let terminal = render(
+            20,
+            1,
+            "\x1b]0;title\x07a\x1b]8;;http://x\x1b\\b\x1b(Bc\x1b[>4;1md",
+        );
+
+        assert_eq!(terminal.text(), "abcd\n");
+    }
+}